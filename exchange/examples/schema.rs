@@ -3,7 +3,11 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use exchange::msg::{ExchangedResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
+use exchange::msg::{
+    AccountExchangedResponse, AllowanceResponse, ExchangeHistoryResponse, ExchangedResponse,
+    ExecuteMsg, FeesResponse, InputTokensResponse, InstantiateMsg, LimitsResponse, MigrateMsg,
+    NativeResponse, OwnerResponse, PausedResponse, QueryMsg, RateResponse, RedeemResponse,
+};
 use exchange::state::State;
 
 fn main() {
@@ -14,7 +18,19 @@ fn main() {
 
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
     export_schema(&schema_for!(ExchangedResponse), &out_dir);
+    export_schema(&schema_for!(RateResponse), &out_dir);
+    export_schema(&schema_for!(InputTokensResponse), &out_dir);
+    export_schema(&schema_for!(NativeResponse), &out_dir);
+    export_schema(&schema_for!(RedeemResponse), &out_dir);
+    export_schema(&schema_for!(FeesResponse), &out_dir);
+    export_schema(&schema_for!(AccountExchangedResponse), &out_dir);
+    export_schema(&schema_for!(ExchangeHistoryResponse), &out_dir);
+    export_schema(&schema_for!(OwnerResponse), &out_dir);
+    export_schema(&schema_for!(PausedResponse), &out_dir);
+    export_schema(&schema_for!(LimitsResponse), &out_dir);
+    export_schema(&schema_for!(AllowanceResponse), &out_dir);
 }
@@ -1,293 +1,3043 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128,
+    coins, from_binary, to_binary, Addr, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Env,
+    MessageInfo, Order, Response, StdError, StdResult, Timestamp, Uint128,
 };
-use cw2::set_contract_version;
-use cw20::{Cw20Contract, Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw0::Expiration;
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{
+    Cw20Contract, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, Denom, MinterResponse,
+    TokenInfoResponse,
+};
+use cw_storage_plus::{Bound, U64Key};
 
 use crate::error::ContractError;
-use crate::msg::{ExchangedResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{State, STATE};
+use crate::msg::{
+    AccountExchangedResponse, AllowanceResponse, ExchangeHistoryResponse, ExchangedResponse,
+    ExecuteMsg, FeesResponse, InputTokenInfo, InputTokensResponse, InstantiateMsg, LimitsResponse,
+    MigrateMsg, NativeRate, NativeResponse, OwnerResponse, PausedResponse, ProposalReceiveMsg,
+    QueryMsg, RateResponse, ReceiveMsg, RedeemResponse, ValueFees,
+};
+use crate::state::{
+    ExchangeEvent, Fee, FeeSource, Limits, PendingOwner, Rate, RedeemConfig, RedeemSource, State,
+    TokenConfig, Window, ACCOUNT_EXCHANGED, ACCOUNT_WINDOWS, DAILY_WINDOW, EXCHANGE_COUNT,
+    EXCHANGE_HISTORY, INPUT_EXCHANGED, INPUT_TOKENS, LEGACY_STATE, NATIVE_RATES, PENDING_OWNER,
+    STATE, VALUE_FEES,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:exchange";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// pagination info for ExchangeHistory and ListInputTokens
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+// length of the window Limits.daily_cap applies to, in seconds
+const DAY: u64 = 24 * 60 * 60;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
-    info: MessageInfo,
+    env: Env,
+    _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
+    let owner = deps.api.addr_validate(&msg.owner)?;
+    let limits = msg.limits.unwrap_or_default();
+    if !limits.is_valid() {
+        return Err(ContractError::InvalidLimits {});
+    }
+    let sobz_token_address = match msg.sobz_token_address {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    let mut input_tokens = vec![];
+    for input in msg.input_tokens {
+        let token = deps.api.addr_validate(&input.address)?;
+        if !input.config.is_valid() {
+            return Err(ContractError::InvalidRate {});
+        }
+        if INPUT_TOKENS.has(deps.storage, &token) {
+            return Err(ContractError::InputTokenExists { token });
+        }
+        INPUT_TOKENS.save(deps.storage, &token, &input.config)?;
+        input_tokens.push(token);
+    }
+    verify_tokens(
+        deps.as_ref(),
+        &env,
+        &input_tokens,
+        sobz_token_address.as_ref(),
+    )?;
     let state = State {
-        owner: msg.owner,
+        owner: Some(owner.clone()),
         exchanged: Uint128::zero(),
-        value_token_address: Addr::from(info.sender.clone()),
-        sobz_token_address: Addr::from(info.sender),
+        sobz_token_address,
+        limits,
+        paused: false,
+        redeemed: Uint128::zero(),
+        redeem: RedeemConfig::default(),
+        fee: None,
+        sobz_fees_collected: Uint128::zero(),
+        native_treasury: None,
     };
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     STATE.save(deps.storage, &state)?;
 
+    let unset = || String::from("unset");
     Ok(Response::new()
         .add_attribute("method", "instantiate")
         .add_attribute("exchanged", "0")
-        .add_attribute("owner", state.owner)
-        .add_attribute("value_token_address", state.value_token_address)
-        .add_attribute("sobz_token_address", state.sobz_token_address))
+        .add_attribute("owner", owner)
+        .add_attribute("input_tokens", input_tokens.len().to_string())
+        .add_attribute(
+            "sobz_token_address",
+            state.sobz_token_address.map_or_else(unset, String::from),
+        ))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Receive(msg) => try_exchange(deps, info, msg),
-        ExecuteMsg::SetTokens {
-            value_token_address,
-            sobz_token_address,
-        } => set_tokens(deps, info, value_token_address, sobz_token_address),
+        ExecuteMsg::Receive(msg) => {
+            // SoBz coming back are redeemed, anything else has to be an input token
+            let state = STATE.load(deps.storage)?;
+            if state.sobz_token_address.as_ref() == Some(&info.sender) {
+                try_redeem(deps, info, msg)
+            } else {
+                try_exchange(deps, env, info, msg)
+            }
+        }
+        ExecuteMsg::ExchangeNative {} => try_exchange_native(deps, env, info),
+        ExecuteMsg::SetSobzToken { sobz_token_address } => {
+            set_sobz_token(deps, env, info, sobz_token_address)
+        }
+        ExecuteMsg::AddInputToken { address, config } => {
+            add_input_token(deps, env, info, address, config)
+        }
+        ExecuteMsg::UpdateInputToken { address, config } => {
+            update_input_token(deps, info, address, config)
+        }
+        ExecuteMsg::SetRate { token, rate } => set_rate(deps, info, token, rate),
+        ExecuteMsg::RemoveInputToken { address } => remove_input_token(deps, info, address),
+        ExecuteMsg::SetNativeRate { denom, rate } => set_native_rate(deps, info, denom, rate),
+        ExecuteMsg::SetNativeTreasury { treasury } => set_native_treasury(deps, info, treasury),
+        ExecuteMsg::WithdrawNative {
+            denom,
+            amount,
+            recipient,
+        } => withdraw_native(deps, info, denom, amount, recipient),
+        ExecuteMsg::SetLimits { limits } => set_limits(deps, info, limits),
+        ExecuteMsg::SetFee { fee } => set_fee(deps, info, fee),
+        ExecuteMsg::SetRedeem { config } => set_redeem(deps, info, config),
+        ExecuteMsg::Pause {} => set_paused(deps, info, true),
+        ExecuteMsg::Unpause {} => set_paused(deps, info, false),
+        ExecuteMsg::ProposeOwner { new_owner, expires } => {
+            propose_owner(deps, env, info, new_owner, expires)
+        }
+        ExecuteMsg::AcceptOwnership {} => accept_ownership(deps, env, info),
+        ExecuteMsg::RenounceOwnership {} => renounce_ownership(deps, info),
     }
 }
 
 pub fn try_exchange(
-    deps: DepsMut,
+    mut deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     msg: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    // did the Cw20ReceiveMsg come from the Value Token Contract? If so, continue.
     let state = STATE.load(deps.storage)?;
-    if info.sender != state.value_token_address {
-        return Err(ContractError::UnauthorizedValueToken {
-            info_sender: info.sender,
-            state_value_token_address: state.value_token_address,
-        });
+    if state.paused {
+        return Err(ContractError::Paused {});
     }
+    let sobz_token_address = state
+        .sobz_token_address
+        .clone()
+        .ok_or(ContractError::TokensNotConfigured {})?;
 
-    // send message to Cw20 Value Token to burn
-    let value_token = Cw20Contract(state.value_token_address);
-    let value_token_msg = value_token.call(Cw20ExecuteMsg::Burn { amount: msg.amount })?;
+    // did the Cw20ReceiveMsg come from a whitelisted Value Token Contract? If so, continue.
+    let value_token_address = info.sender;
+    let config = INPUT_TOKENS
+        .may_load(deps.storage, &value_token_address)?
+        .ok_or_else(|| ContractError::UnauthorizedValueToken {
+            info_sender: value_token_address.clone(),
+        })?;
+    if !config.enabled {
+        return Err(ContractError::InputTokenDisabled {
+            token: value_token_address,
+        });
+    }
 
-    // send message to Cw20 Sobz Token to mint
-    let sobz_token = Cw20Contract(state.sobz_token_address);
+    // and stay within the caps on that token
+    if let Some(max) = config.max_per_tx {
+        if msg.amount > max {
+            return Err(ContractError::InputTxLimitExceeded {
+                amount: msg.amount,
+                max,
+            });
+        }
+    }
+    let token_exchanged = INPUT_EXCHANGED
+        .may_load(deps.storage, &value_token_address)?
+        .unwrap_or_default();
+    if let Some(max) = config.max_total {
+        let remaining = max.checked_sub(token_exchanged).unwrap_or_default();
+        if msg.amount > remaining {
+            return Err(ContractError::InputCapExceeded {
+                amount: msg.amount,
+                remaining,
+            });
+        }
+    }
     // msg: Cw20ReceiveMsg.sender is the keypair that actually initiated the
     // whole transaction; info.sender is the Cw20 contract that emitted the
-    // Cw20ReceiveMsg to us
-    let sobz_token_msg = sobz_token.call(Cw20ExecuteMsg::Mint {
-        recipient: msg.sender.clone(),
+    // Cw20ReceiveMsg to us. The SoBz go to them unless they say otherwise
+    let receive_msg: ReceiveMsg =
+        from_binary(&msg.msg).map_err(|err| ContractError::InvalidReceiveMsg {
+            error: err.to_string(),
+        })?;
+    let (recipient, min_out, proposal_id) = match receive_msg {
+        ReceiveMsg::Send {} => (None, None, None),
+        ReceiveMsg::Exchange { recipient, min_out } => (recipient, min_out, None),
+        ReceiveMsg::FundProposal {
+            proposal,
+            id,
+            min_out,
+        } => (Some(proposal), min_out, Some(id)),
+    };
+    let account = deps.api.addr_validate(&msg.sender)?;
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => account.clone(),
+    };
+
+    let exchange = Exchange {
+        input: Denom::Cw20(value_token_address.clone()),
         amount: msg.amount,
-    })?;
+        rate: config.rate,
+        account,
+        recipient,
+        min_out,
+        proposal_id,
+    };
+    let res = mint_sobz(
+        deps.branch(),
+        env,
+        state,
+        sobz_token_address,
+        exchange,
+        "try_exchange",
+    )?;
+
+    // counted towards the token's cap once the exchange has gone through
+    INPUT_EXCHANGED.save(
+        deps.storage,
+        &value_token_address,
+        &(token_exchanged + msg.amount),
+    )?;
+    Ok(res)
+}
+
+pub fn try_exchange_native(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    let sobz_token_address = state
+        .sobz_token_address
+        .clone()
+        .ok_or(ContractError::TokensNotConfigured {})?;
+
+    let coin = match info.funds.as_slice() {
+        [coin] => coin.clone(),
+        _ => return Err(ContractError::InvalidNativeFunds {}),
+    };
+    let rate = NATIVE_RATES
+        .may_load(deps.storage, &coin.denom)?
+        .ok_or_else(|| ContractError::UnknownDenom {
+            denom: coin.denom.clone(),
+        })?;
+
+    let exchange = Exchange {
+        input: Denom::Native(coin.denom),
+        amount: coin.amount,
+        rate,
+        account: info.sender.clone(),
+        recipient: info.sender,
+        min_out: None,
+        proposal_id: None,
+    };
+    mint_sobz(
+        deps,
+        env,
+        state,
+        sobz_token_address,
+        exchange,
+        "try_exchange_native",
+    )
+}
+
+// Exchange is what mint_sobz needs to know, whichever way the Value came in
+struct Exchange {
+    // the CW20 input token or native coin, and how much of it
+    input: Denom,
+    amount: Uint128,
+    rate: Rate,
+    // who sent it, and who receives the SoBz
+    account: Addr,
+    recipient: Addr,
+    min_out: Option<Uint128>,
+    // the SoBz top up this escrow in the recipient, see ReceiveMsg::FundProposal
+    proposal_id: Option<String>,
+}
+
+// Mints the SoBz an exchange is worth, after fees and within the limits, and
+// disposes of what was exchanged: CW20 tokens are burned, native coins held
+// or forwarded to the native treasury
+fn mint_sobz(
+    deps: DepsMut,
+    env: Env,
+    state: State,
+    sobz_token_address: Addr,
+    exchange: Exchange,
+    method: &str,
+) -> Result<Response, ContractError> {
+    let Exchange {
+        input,
+        amount,
+        rate,
+        account,
+        recipient,
+        min_out,
+        proposal_id,
+    } = exchange;
+
+    // the fee comes out of either the Value tokens or the SoBz they are worth
+    let value_fee = match &state.fee {
+        Some(fee) if fee.taken_from == FeeSource::Value {} => fee.apply(amount),
+        _ => Uint128::zero(),
+    };
+    let burned = amount - value_fee;
+
+    // work out how many SoBz the Value tokens are worth
+    let minted = rate.apply(burned);
+    let sobz_fee = match &state.fee {
+        Some(fee) if fee.taken_from == FeeSource::Sobz {} => fee.apply(minted),
+        _ => Uint128::zero(),
+    };
+    let received = minted - sobz_fee;
+    if received.is_zero() {
+        return Err(ContractError::ExchangeTooSmall { amount });
+    }
+    // in case the rate or fee changed since the user looked
+    if let Some(min_out) = min_out {
+        if received < min_out {
+            return Err(ContractError::MinOutNotMet { received, min_out });
+        }
+    }
+
+    // make sure we stay within the minting caps
+    let allowance = allowance(deps.as_ref(), &state.limits, &account, env.block.time)?;
+    if let Some(max) = allowance.per_tx {
+        if minted > max {
+            return Err(ContractError::TxLimitExceeded { minted, max });
+        }
+    }
+    if let Some(remaining) = allowance.account {
+        if minted > remaining {
+            return Err(ContractError::AccountLimitExceeded { minted, remaining });
+        }
+    }
+    if let Some(remaining) = allowance.daily {
+        if minted > remaining {
+            return Err(ContractError::DailyCapExceeded { minted, remaining });
+        }
+    }
+    if state.limits.max_per_account.is_some() {
+        let window = ACCOUNT_WINDOWS.may_load(deps.storage, &account)?;
+        let window = Window::record(window, env.block.time, state.limits.account_window, minted);
+        ACCOUNT_WINDOWS.save(deps.storage, &account, &window)?;
+    }
+    if state.limits.daily_cap.is_some() {
+        let window = DAILY_WINDOW.may_load(deps.storage)?;
+        let window = Window::record(window, env.block.time, DAY, minted);
+        DAILY_WINDOW.save(deps.storage, &window)?;
+    }
+
+    // send message to Cw20 Value Token to burn, or pass native coins on
+    let mut value_msgs: Vec<CosmosMsg> = vec![];
+    // transfers amount of the input to recipient, for the fee and native treasury
+    let pay = |recipient: &Addr, amount: Uint128| -> StdResult<CosmosMsg> {
+        match &input {
+            Denom::Cw20(token) => Cw20Contract(token.clone()).call(Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            }),
+            Denom::Native(denom) => Ok(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: coins(amount.u128(), denom),
+            }
+            .into()),
+        }
+    };
+    match (&input, &state.native_treasury) {
+        (Denom::Cw20(token), _) => value_msgs
+            .push(Cw20Contract(token.clone()).call(Cw20ExecuteMsg::Burn { amount: burned })?),
+        (Denom::Native(_), Some(treasury)) => value_msgs.push(pay(treasury, burned)?),
+        // held by the exchange
+        (Denom::Native(_), _) => {}
+    }
+
+    // send message to Cw20 Sobz Token to mint
+    let sobz_token = Cw20Contract(sobz_token_address);
+    let sobz_token_msgs = match &proposal_id {
+        None => vec![sobz_token.call(Cw20ExecuteMsg::Mint {
+            recipient: recipient.to_string(),
+            amount: received,
+        })?],
+        // minting doesn't call the proposal contract, so we mint to ourselves
+        // and send them on with a TopUp
+        Some(id) => vec![
+            sobz_token.call(Cw20ExecuteMsg::Mint {
+                recipient: env.contract.address.to_string(),
+                amount: received,
+            })?,
+            sobz_token.call(Cw20ExecuteMsg::Send {
+                contract: recipient.to_string(),
+                amount: received,
+                msg: to_binary(&ProposalReceiveMsg::TopUp {
+                    id: id.clone(),
+                    contributor: Some(account.to_string()),
+                })?,
+            })?,
+        ],
+    };
+
+    // and pay the fee to the treasury
+    let mut fee_msgs = vec![];
+    if let Some(fee) = &state.fee {
+        if !value_fee.is_zero() {
+            fee_msgs.push(pay(&fee.treasury, value_fee)?);
+        }
+        if !sobz_fee.is_zero() {
+            fee_msgs.push(sobz_token.call(Cw20ExecuteMsg::Mint {
+                recipient: fee.treasury.to_string(),
+                amount: sobz_fee,
+            })?);
+        }
+    }
 
-    // update # of valuetokens exchanged counter
+    // update # of sobz received counter, input amounts aren't comparable across tokens
     STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        state.exchanged = state.exchanged + msg.amount;
+        state.exchanged += received;
+        state.sobz_fees_collected += sobz_fee;
         Ok(state)
     })?;
+    let token = match &input {
+        Denom::Cw20(token) => token.to_string(),
+        Denom::Native(denom) => denom.clone(),
+    };
+    if !value_fee.is_zero() {
+        VALUE_FEES.update(deps.storage, &token, |fees| -> StdResult<_> {
+            Ok(fees.unwrap_or_default() + value_fee)
+        })?;
+    }
+
+    // and the account's own counter and the exchange history
+    ACCOUNT_EXCHANGED.update(deps.storage, &account, |exchanged| -> StdResult<_> {
+        Ok(exchanged.unwrap_or_default() + received)
+    })?;
+    let id = EXCHANGE_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    EXCHANGE_COUNT.save(deps.storage, &id)?;
+    let event = ExchangeEvent {
+        id,
+        height: env.block.height,
+        time: env.block.time,
+        token: input,
+        account: account.clone(),
+        recipient: recipient.clone(),
+        amount_in: amount,
+        amount_out: received,
+    };
+    EXCHANGE_HISTORY.save(deps.storage, U64Key::new(id), &event)?;
+
+    Ok(Response::new()
+        .add_attribute("method", method)
+        .add_attribute("token", token)
+        .add_attribute("account", account)
+        .add_attribute("recipient", recipient)
+        .add_attribute("proposal_id", proposal_id.unwrap_or_default())
+        .add_attribute("exchange", amount)
+        .add_attribute("minted", received)
+        .add_attribute("value_fee", value_fee)
+        .add_attribute("sobz_fee", sobz_fee)
+        .add_messages(value_msgs)
+        .add_messages(sobz_token_msgs)
+        .add_messages(fee_msgs))
+}
+
+// only the owner can change the exchange's settings
+fn assert_owner(state: &State, sender: &Addr) -> Result<(), ContractError> {
+    match &state.owner {
+        Some(owner) if owner == sender => Ok(()),
+        Some(owner) => Err(ContractError::Unauthorized {
+            owner: owner.clone(),
+            sender: sender.clone(),
+        }),
+        None => Err(ContractError::NoOwner {}),
+    }
+}
+
+pub fn try_redeem(
+    deps: DepsMut,
+    info: MessageInfo,
+    msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    if !state.redeem.enabled {
+        return Err(ContractError::RedeemDisabled {});
+    }
+    let value_token_address = state
+        .redeem
+        .token
+        .clone()
+        .ok_or(ContractError::RedeemDisabled {})?;
+
+    // work out how many Value tokens the SoBz are worth, less the fee
+    let value = state.redeem.rate.apply(msg.amount);
+    let fee = state.redeem.fee(value);
+    let paid = value.checked_sub(fee).map_err(StdError::from)?;
+    if paid.is_zero() {
+        return Err(ContractError::RedeemTooSmall { amount: msg.amount });
+    }
+
+    // burn the SoBz we were sent, info.sender is the SoBz token
+    let sobz_token = Cw20Contract(info.sender);
+    let sobz_token_msg = sobz_token.call(Cw20ExecuteMsg::Burn { amount: msg.amount })?;
+
+    // and pay out the Value tokens
+    let value_token = Cw20Contract(value_token_address);
+    let value_token_msg = match state.redeem.source {
+        RedeemSource::Mint {} => value_token.call(Cw20ExecuteMsg::Mint {
+            recipient: msg.sender.clone(),
+            amount: paid,
+        })?,
+        RedeemSource::Reserve {} => value_token.call(Cw20ExecuteMsg::Transfer {
+            recipient: msg.sender.clone(),
+            amount: paid,
+        })?,
+    };
+
+    state.redeemed += msg.amount;
+    STATE.save(deps.storage, &state)?;
 
     Ok(Response::new()
-        .add_attribute("method", "try_exchange")
+        .add_attribute("method", "try_redeem")
         .add_attribute("account", msg.sender)
-        .add_attribute("exchange", stringify!(msg.amount))
-        .add_message(value_token_msg)
-        .add_message(sobz_token_msg))
+        .add_attribute("redeem", msg.amount)
+        .add_attribute("paid", paid)
+        .add_attribute("fee", fee)
+        .add_message(sobz_token_msg)
+        .add_message(value_token_msg))
 }
 
-pub fn set_tokens(
+pub fn set_sobz_token(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    value_token_address: Addr,
     sobz_token_address: Addr,
 ) -> Result<Response, ContractError> {
     let mut state = STATE.load(deps.storage)?;
 
     // Not just anybody can set the token addresses! You've got to be the owner
-    if info.sender != state.owner {
-        return Err(ContractError::UnauthorizedSetToken {
-            owner: state.owner,
-            sender: info.sender,
-        });
+    match &state.owner {
+        Some(owner) if *owner == info.sender => {}
+        Some(owner) => {
+            return Err(ContractError::UnauthorizedSetToken {
+                owner: owner.clone(),
+                sender: info.sender,
+            })
+        }
+        None => return Err(ContractError::NoOwner {}),
     }
-    state.value_token_address = value_token_address.clone();
-    state.sobz_token_address = sobz_token_address.clone();
+    let input_tokens = INPUT_TOKENS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(|key| String::from_utf8(key).map(Addr::unchecked))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(StdError::from)?;
+    verify_tokens(
+        deps.as_ref(),
+        &env,
+        &input_tokens,
+        Some(&sobz_token_address),
+    )?;
+    state.sobz_token_address = Some(sobz_token_address.clone());
 
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     STATE.save(deps.storage, &state)?;
 
     Ok(Response::new()
-        .add_attribute("method", "set_tokens")
-        .add_attribute("value_token", value_token_address)
+        .add_attribute("method", "set_sobz_token")
         .add_attribute("sobz_token", sobz_token_address))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetExchanged {} => to_binary(&query_exchanged(deps)?),
+// Makes sure the tokens are CW20 contracts, that we are allowed to mint SoBz,
+// and that every input token has the same decimals as SoBz, as a rate in base
+// units assumes that
+fn verify_tokens(
+    deps: Deps,
+    env: &Env,
+    input_tokens: &[Addr],
+    sobz_token: Option<&Addr>,
+) -> Result<(), ContractError> {
+    let token_info = |token: &Addr| -> Result<TokenInfoResponse, ContractError> {
+        deps.querier
+            .query_wasm_smart(token.to_string(), &Cw20QueryMsg::TokenInfo {})
+            .map_err(|_| ContractError::NotCw20Token {
+                address: token.clone(),
+            })
+    };
+    let input_infos = input_tokens
+        .iter()
+        .map(token_info)
+        .collect::<Result<Vec<_>, _>>()?;
+    let sobz_info = sobz_token.map(token_info).transpose()?;
+
+    if let Some(sobz_token) = sobz_token {
+        let minter: Option<MinterResponse> = deps
+            .querier
+            .query_wasm_smart(sobz_token.to_string(), &Cw20QueryMsg::Minter {})?;
+        let minter = minter.map(|m| m.minter);
+        if minter.as_deref() != Some(env.contract.address.as_str()) {
+            return Err(ContractError::NotSobzMinter { minter });
+        }
+    }
+    if let Some(sobz_info) = sobz_info {
+        for value_info in input_infos {
+            if value_info.decimals != sobz_info.decimals {
+                return Err(ContractError::DecimalsMismatch {
+                    value_decimals: value_info.decimals,
+                    sobz_decimals: sobz_info.decimals,
+                });
+            }
+        }
     }
+    Ok(())
 }
 
-fn query_exchanged(deps: Deps) -> StdResult<ExchangedResponse> {
+pub fn add_input_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+    config: TokenConfig,
+) -> Result<Response, ContractError> {
     let state = STATE.load(deps.storage)?;
-    Ok(ExchangedResponse {
-        exchanged: state.exchanged,
-    })
-}
+    assert_owner(&state, &info.sender)?;
 
-#[cfg(test)]
-mod tests {
-    use crate::msg::ReceiveMsg;
+    let token = deps.api.addr_validate(&address)?;
+    if !config.is_valid() {
+        return Err(ContractError::InvalidRate {});
+    }
+    if INPUT_TOKENS.has(deps.storage, &token) {
+        return Err(ContractError::InputTokenExists { token });
+    }
+    verify_tokens(
+        deps.as_ref(),
+        &env,
+        std::slice::from_ref(&token),
+        state.sobz_token_address.as_ref(),
+    )?;
+    INPUT_TOKENS.save(deps.storage, &token, &config)?;
 
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary, Addr, CosmosMsg, WasmMsg};
+    Ok(Response::new()
+        .add_attribute("method", "add_input_token")
+        .add_attribute("token", token))
+}
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(&[]);
+pub fn update_input_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    config: TokenConfig,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
 
-        let msg = InstantiateMsg {
-            owner: Addr::unchecked("Dorium"),
-        };
-        let info = mock_info("creator", &coins(1000, "earth"));
+    let token = deps.api.addr_validate(&address)?;
+    if !config.is_valid() {
+        return Err(ContractError::InvalidRate {});
+    }
+    if !INPUT_TOKENS.has(deps.storage, &token) {
+        return Err(ContractError::InputTokenNotFound { token });
+    }
+    INPUT_TOKENS.save(deps.storage, &token, &config)?;
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-        assert_eq!("Dorium", res.attributes[2].value);
+    Ok(Response::new()
+        .add_attribute("method", "update_input_token")
+        .add_attribute("token", token)
+        .add_attribute("numerator", config.rate.numerator)
+        .add_attribute("denominator", config.rate.denominator)
+        .add_attribute("enabled", config.enabled.to_string()))
+}
 
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
-        let value: ExchangedResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::zero(), value.exchanged);
+pub fn set_rate(
+    deps: DepsMut,
+    info: MessageInfo,
+    token: String,
+    rate: Rate,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
+
+    let token = deps.api.addr_validate(&token)?;
+    if !rate.is_valid() {
+        return Err(ContractError::InvalidRate {});
     }
+    let mut config = INPUT_TOKENS
+        .may_load(deps.storage, &token)?
+        .ok_or_else(|| ContractError::InputTokenNotFound {
+            token: token.clone(),
+        })?;
+    config.rate = rate;
+    INPUT_TOKENS.save(deps.storage, &token, &config)?;
 
-    #[test]
-    fn exchange_works() {
-        let mut deps = mock_dependencies(&coins(2, "token"));
+    Ok(Response::new()
+        .add_attribute("method", "set_rate")
+        .add_attribute("token", token)
+        .add_attribute("numerator", config.rate.numerator)
+        .add_attribute("denominator", config.rate.denominator))
+}
 
-        // Instantiate the exchange
-        let msg = InstantiateMsg {
-            owner: Addr::unchecked("Dorium"),
-        };
-        let info = mock_info("Dorium", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+pub fn remove_input_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
 
-        // After instantiating, we need to set the addresses of the CW20 tokens
-        let set_tokens_msg = ExecuteMsg::SetTokens {
-            value_token_address: Addr::unchecked("TREE"),
-            sobz_token_address: Addr::unchecked("SOBZ"),
-        };
-        execute(deps.as_mut(), mock_env(), info, set_tokens_msg).unwrap();
+    let token = deps.api.addr_validate(&address)?;
+    if !INPUT_TOKENS.has(deps.storage, &token) {
+        return Err(ContractError::InputTokenNotFound { token });
+    }
+    // INPUT_EXCHANGED is kept, so the cap still counts if it is added again
+    INPUT_TOKENS.remove(deps.storage, &token);
 
-        let info = mock_info("TREE", &coins(2, "token"));
-        let receive_msg = Cw20ReceiveMsg {
-            sender: String::from("some user"),
-            amount: Uint128::new(18),
-            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
-        };
-        let execute_msg = ExecuteMsg::Receive(receive_msg);
-        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+    Ok(Response::new()
+        .add_attribute("method", "remove_input_token")
+        .add_attribute("token", token))
+}
 
-        // check that exchange contract told TREE and SOBZ CW20 contracts to Mint and Burn respectively
-        println!("{:?}", res.messages);
-        let value_token_msg = res.messages[0].clone().msg;
-        assert_eq!(
-            value_token_msg,
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: String::from("TREE"),
-                msg: to_binary(&Cw20ExecuteMsg::Burn {
-                    amount: Uint128::new(18)
-                })
-                .unwrap(),
-                funds: vec![],
-            })
-        );
-        let sobz_token_msg = res.messages[1].clone().msg;
-        assert_eq!(
-            sobz_token_msg,
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: String::from("SOBZ"),
-                msg: to_binary(&Cw20ExecuteMsg::Mint {
-                    recipient: String::from("some user"),
-                    amount: Uint128::new(18)
-                })
-                .unwrap(),
-                funds: vec![],
-            })
-        );
+pub fn set_native_rate(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    rate: Option<Rate>,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
 
-        // exchange contract's counter should've increased by <amount>
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
-        let value: ExchangedResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(18), value.exchanged);
+    match rate {
+        Some(rate) if !rate.is_valid() => return Err(ContractError::InvalidRate {}),
+        Some(rate) => NATIVE_RATES.save(deps.storage, &denom, &rate)?,
+        None => NATIVE_RATES.remove(deps.storage, &denom),
     }
-    #[test]
-    fn exchange_unauthorized_cw20_denied() {
-        let mut deps = mock_dependencies(&coins(2, "token"));
 
-        // Instantiate the exchange
-        let msg = InstantiateMsg {
-            owner: Addr::unchecked("Dorium"),
-        };
-        let info = mock_info("Dorium", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    Ok(Response::new()
+        .add_attribute("method", "set_native_rate")
+        .add_attribute("denom", denom))
+}
 
-        // If we don't set the addresses of the CW20 tokens, the CW20 token
-        // addresses allowed will be set to "Dorium". But we're going to send a
-        // message from the CW20 smart contract "TREE". So it should fail.
+pub fn set_native_treasury(
+    deps: DepsMut,
+    info: MessageInfo,
+    treasury: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
 
-        let info = mock_info("TREE", &coins(2, "token"));
-        let receive_msg = Cw20ReceiveMsg {
-            sender: String::from("some user"),
-            amount: Uint128::new(18),
-            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
-        };
-        let execute_msg = ExecuteMsg::Receive(receive_msg);
-        let res = execute(deps.as_mut(), mock_env(), info, execute_msg);
+    state.native_treasury = match treasury {
+        Some(treasury) => Some(deps.api.addr_validate(&treasury)?),
+        None => None,
+    };
+    STATE.save(deps.storage, &state)?;
 
-        // check that exchange contract gave an error
-        println!("{:?}", res);
-        assert_eq!(
-            res,
-            Err(ContractError::UnauthorizedValueToken {
-                info_sender: Addr::unchecked("TREE"),
-                state_value_token_address: Addr::unchecked("Dorium")
-            })
-        );
+    Ok(Response::new().add_attribute("method", "set_native_treasury"))
+}
 
-        // exchange contract's counter should've increased by <amount>
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
-        let value: ExchangedResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(0), value.exchanged);
-    }
-    #[test]
-    fn only_owner_can_set_tokens() {
-        let mut deps = mock_dependencies(&coins(2, "token"));
+pub fn withdraw_native(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    amount: Uint128,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
 
-        // Instantiate the exchange
-        let msg = InstantiateMsg {
-            owner: Addr::unchecked("Dorium"),
-        };
-        let info = mock_info("Dorium", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let msg = BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: coins(amount.u128(), &denom),
+    };
 
-        // After instantiating, we need to set the addresses of the CW20 tokens
-        let set_tokens_msg = ExecuteMsg::SetTokens {
-            value_token_address: Addr::unchecked("TREE"),
-            sobz_token_address: Addr::unchecked("SOBZ"),
-        };
+    Ok(Response::new()
+        .add_attribute("method", "withdraw_native")
+        .add_attribute("denom", denom)
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient)
+        .add_message(msg))
+}
+
+pub fn set_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee: Option<Fee>,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
+
+    if let Some(fee) = &fee {
+        if !fee.is_valid() {
+            return Err(ContractError::InvalidFee {});
+        }
+        deps.api.addr_validate(fee.treasury.as_str())?;
+    }
+    state.fee = fee;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attribute("method", "set_fee"))
+}
+
+pub fn set_redeem(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: RedeemConfig,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
+
+    if !config.is_valid() {
+        return Err(ContractError::InvalidRedeemConfig {});
+    }
+    if let Some(token) = &config.token {
+        deps.api.addr_validate(token.as_str())?;
+    }
+    state.redeem = config;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_redeem")
+        .add_attribute("enabled", state.redeem.enabled.to_string()))
+}
+
+pub fn set_limits(
+    deps: DepsMut,
+    info: MessageInfo,
+    limits: Limits,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+
+    assert_owner(&state, &info.sender)?;
+    if !limits.is_valid() {
+        return Err(ContractError::InvalidLimits {});
+    }
+    state.limits = limits;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attribute("method", "set_limits"))
+}
+
+pub fn set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+
+    assert_owner(&state, &info.sender)?;
+    state.paused = paused;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attribute("method", if paused { "pause" } else { "unpause" }))
+}
+
+pub fn propose_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_owner: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
+
+    if let Some(expires) = expires {
+        if expires.is_expired(&env.block) {
+            return Err(ContractError::OwnershipTransferExpired {});
+        }
+    }
+    let pending = PendingOwner {
+        owner: deps.api.addr_validate(&new_owner)?,
+        expires,
+    };
+    PENDING_OWNER.save(deps.storage, &pending)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "propose_owner")
+        .add_attribute("pending_owner", pending.owner))
+}
+
+pub fn accept_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_OWNER
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingOwner {})?;
+    if info.sender != pending.owner {
+        return Err(ContractError::NotPendingOwner {
+            pending_owner: pending.owner,
+            sender: info.sender,
+        });
+    }
+    if let Some(expires) = pending.expires {
+        if expires.is_expired(&env.block) {
+            return Err(ContractError::OwnershipTransferExpired {});
+        }
+    }
+
+    STATE.update(deps.storage, |mut state| -> StdResult<_> {
+        state.owner = Some(pending.owner.clone());
+        Ok(state)
+    })?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("method", "accept_ownership")
+        .add_attribute("owner", pending.owner))
+}
+
+pub fn renounce_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    assert_owner(&state, &info.sender)?;
+
+    state.owner = None;
+    STATE.save(deps.storage, &state)?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("method", "renounce_ownership"))
+}
+
+// how many SoBz account can still mint under each of the limits at time now
+fn allowance(
+    deps: Deps,
+    limits: &Limits,
+    account: &Addr,
+    now: Timestamp,
+) -> StdResult<AllowanceResponse> {
+    let account_minted = match ACCOUNT_WINDOWS.may_load(deps.storage, account)? {
+        Some(window) => window.minted_at(now, limits.account_window),
+        None => Uint128::zero(),
+    };
+    let daily_minted = match DAILY_WINDOW.may_load(deps.storage)? {
+        Some(window) => window.minted_at(now, DAY),
+        None => Uint128::zero(),
+    };
+    let remaining = |max: Uint128, minted: Uint128| max.checked_sub(minted).unwrap_or_default();
+
+    let per_tx = limits.max_per_tx;
+    let account_left = limits
+        .max_per_account
+        .map(|max| remaining(max, account_minted));
+    let daily = limits.daily_cap.map(|max| remaining(max, daily_minted));
+    let remaining = vec![per_tx, account_left, daily]
+        .into_iter()
+        .flatten()
+        .min();
+    Ok(AllowanceResponse {
+        address: account.to_string(),
+        per_tx,
+        account: account_left,
+        daily,
+        remaining,
+    })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: stored.contract,
+        });
+    }
+    // refuse downgrades, and versions we can't make sense of
+    let previous = parse_version(&stored.version);
+    if previous.is_none() || previous > parse_version(CONTRACT_VERSION) {
+        return Err(ContractError::CannotMigrateVersion {
+            previous_version: stored.version,
+        });
+    }
+
+    if previous < Some((0, 2, 0)) {
+        let legacy = LEGACY_STATE.load(deps.storage)?;
+        if let Some((token, config)) = legacy.input_token() {
+            INPUT_TOKENS.save(deps.storage, &token, &config)?;
+            // everything 0.1 exchanged was this token, so max_total counts it
+            INPUT_EXCHANGED.save(deps.storage, &token, &legacy.exchanged)?;
+        }
+        STATE.save(deps.storage, &State::from(legacy))?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+// splits a "major.minor.patch" version so versions can be compared
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|p| p.parse::<u64>().ok());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => {
+            Some((major, minor, patch))
+        }
+        _ => None,
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetExchanged {} => to_binary(&query_exchanged(deps)?),
+        QueryMsg::GetRate { token } => to_binary(&query_rate(deps, token)?),
+        QueryMsg::ListInputTokens { start_after, limit } => {
+            to_binary(&query_input_tokens(deps, start_after, limit)?)
+        }
+        QueryMsg::GetNative {} => to_binary(&query_native(deps)?),
+        QueryMsg::GetFees {} => to_binary(&query_fees(deps)?),
+        QueryMsg::GetRedeem {} => to_binary(&query_redeem(deps)?),
+        QueryMsg::GetAccountExchanged { address } => {
+            to_binary(&query_account_exchanged(deps, address)?)
+        }
+        QueryMsg::Owner {} => to_binary(&query_owner(deps)?),
+        QueryMsg::GetPaused {} => to_binary(&query_paused(deps)?),
+        QueryMsg::GetLimits {} => to_binary(&query_limits(deps)?),
+        QueryMsg::GetAllowance { address } => to_binary(&query_allowance(deps, env, address)?),
+        QueryMsg::ExchangeHistory { start_after, limit } => {
+            to_binary(&query_exchange_history(deps, start_after, limit)?)
+        }
+    }
+}
+
+fn query_exchanged(deps: Deps) -> StdResult<ExchangedResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(ExchangedResponse {
+        exchanged: state.exchanged,
+    })
+}
+
+fn query_rate(deps: Deps, token: String) -> StdResult<RateResponse> {
+    let token = deps.api.addr_validate(&token)?;
+    let config = INPUT_TOKENS.load(deps.storage, &token)?;
+    Ok(RateResponse { rate: config.rate })
+}
+
+fn query_input_tokens(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<InputTokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(address) => Some(Bound::exclusive(deps.api.addr_validate(&address)?.as_str())),
+        None => None,
+    };
+    let tokens: StdResult<Vec<_>> = INPUT_TOKENS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, config) = item?;
+            let address = Addr::unchecked(String::from_utf8(key)?);
+            let exchanged = INPUT_EXCHANGED
+                .may_load(deps.storage, &address)?
+                .unwrap_or_default();
+            Ok(InputTokenInfo {
+                address,
+                config,
+                exchanged,
+            })
+        })
+        .collect();
+    Ok(InputTokensResponse { tokens: tokens? })
+}
+
+fn query_native(deps: Deps) -> StdResult<NativeResponse> {
+    let state = STATE.load(deps.storage)?;
+    let rates: StdResult<Vec<_>> = NATIVE_RATES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, rate) = item?;
+            Ok(NativeRate {
+                denom: String::from_utf8(denom)?,
+                rate,
+            })
+        })
+        .collect();
+    Ok(NativeResponse {
+        rates: rates?,
+        treasury: state.native_treasury,
+    })
+}
+
+fn query_fees(deps: Deps) -> StdResult<FeesResponse> {
+    let state = STATE.load(deps.storage)?;
+    let value_collected: StdResult<Vec<_>> = VALUE_FEES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (token, collected) = item?;
+            Ok(ValueFees {
+                token: String::from_utf8(token)?,
+                collected,
+            })
+        })
+        .collect();
+    Ok(FeesResponse {
+        fee: state.fee,
+        value_collected: value_collected?,
+        sobz_collected: state.sobz_fees_collected,
+    })
+}
+
+fn query_redeem(deps: Deps) -> StdResult<RedeemResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(RedeemResponse {
+        config: state.redeem,
+        redeemed: state.redeemed,
+    })
+}
+
+fn query_owner(deps: Deps) -> StdResult<OwnerResponse> {
+    let state = STATE.load(deps.storage)?;
+    let pending = PENDING_OWNER.may_load(deps.storage)?;
+    Ok(OwnerResponse {
+        owner: state.owner,
+        pending_owner: pending.as_ref().map(|p| p.owner.clone()),
+        pending_expires: pending.and_then(|p| p.expires),
+    })
+}
+
+fn query_paused(deps: Deps) -> StdResult<PausedResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(PausedResponse {
+        paused: state.paused,
+    })
+}
+
+fn query_limits(deps: Deps) -> StdResult<LimitsResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(LimitsResponse {
+        limits: state.limits,
+    })
+}
+
+fn query_allowance(deps: Deps, env: Env, address: String) -> StdResult<AllowanceResponse> {
+    let state = STATE.load(deps.storage)?;
+    let account = deps.api.addr_validate(&address)?;
+    allowance(deps, &state.limits, &account, env.block.time)
+}
+
+fn query_account_exchanged(deps: Deps, address: String) -> StdResult<AccountExchangedResponse> {
+    let account = deps.api.addr_validate(&address)?;
+    let exchanged = ACCOUNT_EXCHANGED
+        .may_load(deps.storage, &account)?
+        .unwrap_or_default();
+    Ok(AccountExchangedResponse { address, exchanged })
+}
+
+fn query_exchange_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ExchangeHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+    let events: StdResult<Vec<_>> = EXCHANGE_HISTORY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, event)| event))
+        .collect();
+    Ok(ExchangeHistoryResponse { events: events? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
+    use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{
+        coins, from_binary, from_slice, Addr, Coin, ContractResult, CosmosMsg, Empty, OwnedDeps,
+        Querier, QuerierResult, QueryRequest, Storage, SystemError, SystemResult, WasmMsg,
+        WasmQuery,
+    };
+    use std::collections::HashMap;
+    use std::marker::PhantomData;
+
+    use crate::msg::InputToken;
+    use crate::state::Rate;
+
+    // MockQuerier can't answer wasm queries in this cosmwasm-std version, so
+    // this answers TokenInfo and Minter queries for the CW20 tokens set with
+    // mock_cw20_tokens, and passes everything else on
+    struct Cw20Querier {
+        base: MockQuerier,
+        // decimals of each token
+        tokens: HashMap<String, u8>,
+        minter: String,
+    }
+
+    impl Querier for Cw20Querier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let (contract_addr, msg) = match from_slice(bin_request) {
+                Ok(QueryRequest::<Empty>::Wasm(WasmQuery::Smart { contract_addr, msg })) => {
+                    (contract_addr, msg)
+                }
+                _ => return self.base.raw_query(bin_request),
+            };
+            let decimals = match self.tokens.get(&contract_addr) {
+                Some(decimals) => *decimals,
+                None => {
+                    return SystemResult::Err(SystemError::NoSuchContract {
+                        addr: contract_addr,
+                    })
+                }
+            };
+            let res = match from_binary(&msg).unwrap() {
+                Cw20QueryMsg::TokenInfo {} => to_binary(&TokenInfoResponse {
+                    name: contract_addr.clone(),
+                    symbol: contract_addr,
+                    decimals,
+                    total_supply: Uint128::zero(),
+                }),
+                Cw20QueryMsg::Minter {} => to_binary(&Some(MinterResponse {
+                    minter: self.minter.clone(),
+                    cap: None,
+                })),
+                _ => panic!("unexpected cw20 query"),
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        }
+    }
+
+    // like cosmwasm_std::testing::mock_dependencies, but CW20 tokens can be mocked
+    fn mock_dependencies(
+        contract_balance: &[Coin],
+    ) -> OwnedDeps<MockStorage, MockApi, Cw20Querier> {
+        OwnedDeps {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            querier: Cw20Querier {
+                base: MockQuerier::new(&[(MOCK_CONTRACT_ADDR, contract_balance)]),
+                tokens: HashMap::new(),
+                minter: String::new(),
+            },
+            custom_query_type: PhantomData,
+        }
+    }
+
+    // answers TokenInfo and Minter queries for the given CW20 tokens, with
+    // their decimals and minter
+    fn mock_cw20_tokens(querier: &mut Cw20Querier, tokens: &[(&str, u8)], minter: &str) {
+        querier.tokens = tokens.iter().map(|(a, d)| (a.to_string(), *d)).collect();
+        querier.minter = minter.to_string();
+    }
+
+    // an enabled input token without caps
+    fn input_token(address: &str, rate: Rate) -> InputToken {
+        InputToken {
+            address: String::from(address),
+            config: TokenConfig {
+                rate,
+                max_per_tx: None,
+                max_total: None,
+                enabled: true,
+            },
+        }
+    }
+
+    fn one_to_one() -> Rate {
+        Rate {
+            numerator: Uint128::new(1),
+            denominator: Uint128::new(1),
+        }
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![],
+            sobz_token_address: None,
+        };
+        let info = mock_info("creator", &coins(1000, "earth"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!("Dorium", res.attributes[2].value);
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
+        let value: ExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.exchanged);
+    }
+
+    #[test]
+    fn exchange_works() {
+        let mut deps = mock_dependencies(&coins(2, "token"));
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        // Instantiate the exchange
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // After instantiating, we need to set the addresses of the CW20 tokens
+        let set_tokens_msg = ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked("SOBZ"),
+        };
+        execute(deps.as_mut(), mock_env(), info, set_tokens_msg).unwrap();
+
+        let info = mock_info("TREE", &coins(2, "token"));
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(18),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let execute_msg = ExecuteMsg::Receive(receive_msg);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+
+        // check that exchange contract told TREE and SOBZ CW20 contracts to Mint and Burn respectively
+        println!("{:?}", res.messages);
+        let value_token_msg = res.messages[0].clone().msg;
+        assert_eq!(
+            value_token_msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("TREE"),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::new(18)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        let sobz_token_msg = res.messages[1].clone().msg;
+        assert_eq!(
+            sobz_token_msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("some user"),
+                    amount: Uint128::new(18)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // exchange contract's counter should've increased by <amount>
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
+        let value: ExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(18), value.exchanged);
+    }
+    #[test]
+    fn exchange_unauthorized_cw20_denied() {
+        let mut deps = mock_dependencies(&coins(2, "token"));
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("VALUE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        // Instantiate the exchange
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("VALUE", one_to_one())],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // If we don't set the addresses of the CW20 tokens, nothing can be
+        // exchanged at all, not even from the instantiator.
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(18),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let execute_msg = ExecuteMsg::Receive(receive_msg);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), execute_msg.clone());
+        assert_eq!(res, Err(ContractError::TokensNotConfigured {}));
+
+        // Once they are set, we're going to send a message from the CW20 smart
+        // contract "TREE", which isn't the Value token. So it should fail.
+        let set_tokens_msg = ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked("SOBZ"),
+        };
+        execute(deps.as_mut(), mock_env(), info, set_tokens_msg).unwrap();
+
+        let info = mock_info("TREE", &coins(2, "token"));
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg);
+
+        // check that exchange contract gave an error
+        println!("{:?}", res);
+        assert_eq!(
+            res,
+            Err(ContractError::UnauthorizedValueToken {
+                info_sender: Addr::unchecked("TREE"),
+            })
+        );
+
+        // exchange contract's counter should've increased by <amount>
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
+        let value: ExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.exchanged);
+    }
+    #[test]
+    fn only_owner_can_set_tokens() {
+        let mut deps = mock_dependencies(&coins(2, "token"));
+
+        // Instantiate the exchange
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // After instantiating, we need to set the addresses of the CW20 tokens
+        let set_tokens_msg = ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked("SOBZ"),
+        };
         let info = mock_info("Attacker", &coins(2, "token"));
         let res = execute(deps.as_mut(), mock_env(), info, set_tokens_msg);
         assert_eq!(
             res,
-            Err(ContractError::UnauthorizedSetToken {
+            Err(ContractError::UnauthorizedSetToken {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("Attacker")
+            })
+        );
+    }
+
+    #[test]
+    fn exchange_applies_rate() {
+        let mut deps = mock_dependencies(&coins(2, "token"));
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        // 3 SoBz for every 2 Value tokens
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token(
+                "TREE",
+                Rate {
+                    numerator: Uint128::new(3),
+                    denominator: Uint128::new(2),
+                },
+            )],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let set_tokens_msg = ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked("SOBZ"),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), set_tokens_msg).unwrap();
+
+        // 7 Value tokens are worth 10.5 SoBz, which is rounded down
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(7),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let tree = mock_info("TREE", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            tree.clone(),
+            ExecuteMsg::Receive(receive_msg),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("some user"),
+                    amount: Uint128::new(10)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // at 1 SoBz for 3 Value tokens, 2 Value tokens aren't worth anything
+        let set_rate_msg = ExecuteMsg::SetRate {
+            token: String::from("TREE"),
+            rate: Rate {
+                numerator: Uint128::new(1),
+                denominator: Uint128::new(3),
+            },
+        };
+        execute(deps.as_mut(), mock_env(), info, set_rate_msg).unwrap();
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(2),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            tree,
+            ExecuteMsg::Receive(receive_msg),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::ExchangeTooSmall {
+                amount: Uint128::new(2)
+            })
+        );
+
+        let msg = QueryMsg::ListInputTokens {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: InputTokensResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(3), value.tokens[0].config.rate.denominator);
+        assert_eq!(Uint128::new(7), value.tokens[0].exchanged);
+        let msg = QueryMsg::GetRate {
+            token: String::from("TREE"),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RateResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(3), value.rate.denominator);
+    }
+
+    #[test]
+    fn only_owner_can_update_input_tokens() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let update_msg = ExecuteMsg::UpdateInputToken {
+            address: String::from("TREE"),
+            config: input_token("TREE", one_to_one()).config,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("Attacker", &[]),
+            update_msg.clone(),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("Attacker")
+            })
+        );
+
+        // only tokens that were added can be updated
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), update_msg);
+        assert_eq!(
+            res,
+            Err(ContractError::InputTokenNotFound {
+                token: Addr::unchecked("TREE")
+            })
+        );
+
+        // a zero denominator doesn't make sense
+        let update_msg = ExecuteMsg::UpdateInputToken {
+            address: String::from("TREE"),
+            config: input_token(
+                "TREE",
+                Rate {
+                    numerator: Uint128::new(1),
+                    denominator: Uint128::zero(),
+                },
+            )
+            .config,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), update_msg);
+        assert_eq!(res, Err(ContractError::InvalidRate {}));
+
+        // the same goes for setting just the rate
+        let set_rate_msg = ExecuteMsg::SetRate {
+            token: String::from("TREE"),
+            rate: one_to_one(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("Attacker", &[]),
+            set_rate_msg.clone(),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("Attacker")
+            })
+        );
+        let res = execute(deps.as_mut(), mock_env(), info, set_rate_msg);
+        assert_eq!(
+            res,
+            Err(ContractError::InputTokenNotFound {
+                token: Addr::unchecked("TREE")
+            })
+        );
+    }
+
+    #[test]
+    fn exchanges_are_tracked_per_account() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token(
+                "TREE",
+                Rate {
+                    numerator: Uint128::new(2),
+                    denominator: Uint128::new(1),
+                },
+            )],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let set_tokens_msg = ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked("SOBZ"),
+        };
+        execute(deps.as_mut(), mock_env(), info, set_tokens_msg).unwrap();
+
+        // alice exchanges twice, bob once
+        for (sender, amount) in &[("alice", 5), ("bob", 7), ("alice", 3)] {
+            let receive_msg = Cw20ReceiveMsg {
+                sender: String::from(*sender),
+                amount: Uint128::new(*amount),
+                msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+            };
+            let info = mock_info("TREE", &[]);
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Receive(receive_msg),
+            )
+            .unwrap();
+        }
+
+        let msg = QueryMsg::GetAccountExchanged {
+            address: String::from("alice"),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AccountExchangedResponse = from_binary(&res).unwrap();
+        // 8 TREE at 2 SoBz each
+        assert_eq!(Uint128::new(16), value.exchanged);
+
+        // someone who never exchanged anything
+        let msg = QueryMsg::GetAccountExchanged {
+            address: String::from("carol"),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AccountExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.exchanged);
+
+        // page through the history
+        let msg = QueryMsg::ExchangeHistory {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExchangeHistoryResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.events.len());
+        assert_eq!(Addr::unchecked("bob"), value.events[1].account);
+        assert_eq!(Uint128::new(14), value.events[1].amount_out);
+
+        let msg = QueryMsg::ExchangeHistory {
+            start_after: Some(value.events[1].id),
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExchangeHistoryResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.events.len());
+        assert_eq!(3, value.events[0].id);
+        assert_eq!(mock_env().block.height, value.events[0].height);
+        assert_eq!(Uint128::new(3), value.events[0].amount_in);
+    }
+
+    #[test]
+    fn exchange_respects_limits() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: Some(Limits {
+                max_per_tx: Some(Uint128::new(10)),
+                max_per_account: Some(Uint128::new(15)),
+                account_window: 100,
+                daily_cap: Some(Uint128::new(25)),
+            }),
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let set_tokens_msg = ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked("SOBZ"),
+        };
+        execute(deps.as_mut(), mock_env(), info, set_tokens_msg).unwrap();
+
+        let exchange = |deps: DepsMut, env: Env, sender: &str, amount: u128| {
+            let receive_msg = Cw20ReceiveMsg {
+                sender: String::from(sender),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+            };
+            let info = mock_info("TREE", &[]);
+            execute(deps, env, info, ExecuteMsg::Receive(receive_msg))
+        };
+
+        let res = exchange(deps.as_mut(), mock_env(), "alice", 11);
+        assert_eq!(
+            res,
+            Err(ContractError::TxLimitExceeded {
+                minted: Uint128::new(11),
+                max: Uint128::new(10)
+            })
+        );
+        exchange(deps.as_mut(), mock_env(), "alice", 10).unwrap();
+        let res = exchange(deps.as_mut(), mock_env(), "alice", 6);
+        assert_eq!(
+            res,
+            Err(ContractError::AccountLimitExceeded {
+                minted: Uint128::new(6),
+                remaining: Uint128::new(5)
+            })
+        );
+        exchange(deps.as_mut(), mock_env(), "bob", 10).unwrap();
+
+        // only 5 left for today
+        let msg = QueryMsg::GetAllowance {
+            address: String::from("carol"),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AllowanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Uint128::new(10)), value.per_tx);
+        assert_eq!(Some(Uint128::new(15)), value.account);
+        assert_eq!(Some(Uint128::new(5)), value.daily);
+        assert_eq!(Some(Uint128::new(5)), value.remaining);
+        let res = exchange(deps.as_mut(), mock_env(), "carol", 6);
+        assert_eq!(
+            res,
+            Err(ContractError::DailyCapExceeded {
+                minted: Uint128::new(6),
+                remaining: Uint128::new(5)
+            })
+        );
+
+        // alice uses the rest of her allowance halfway through her window
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(50);
+        exchange(deps.as_mut(), env.clone(), "alice", 5).unwrap();
+
+        // the window rolls along, so only the first 10 have left it after 100 seconds
+        env.block.time = mock_env().block.time.plus_seconds(101);
+        let msg = QueryMsg::GetAllowance {
+            address: String::from("alice"),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: AllowanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Uint128::new(10)), value.account);
+        assert_eq!(Some(Uint128::zero()), value.remaining);
+        env.block.time = mock_env().block.time.plus_seconds(160);
+        let msg = QueryMsg::GetAllowance {
+            address: String::from("alice"),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: AllowanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Uint128::new(15)), value.account);
+
+        // and once the hour of those exchanges has left the last 24 hours, the
+        // daily cap has room again
+        env.block.time = mock_env().block.time.plus_seconds(DAY + 60 * 60);
+        exchange(deps.as_mut(), env.clone(), "carol", 10).unwrap();
+
+        // even a window longer than time itself just never lets go
+        let set_limits_msg = ExecuteMsg::SetLimits {
+            limits: Limits {
+                max_per_tx: None,
+                max_per_account: Some(Uint128::new(15)),
+                account_window: u64::MAX,
+                daily_cap: None,
+            },
+        };
+        let info = mock_info("Dorium", &[]);
+        execute(deps.as_mut(), env.clone(), info, set_limits_msg).unwrap();
+        exchange(deps.as_mut(), env.clone(), "dave", 15).unwrap();
+        let res = exchange(deps.as_mut(), env, "dave", 1);
+        assert_eq!(
+            res,
+            Err(ContractError::AccountLimitExceeded {
+                minted: Uint128::new(1),
+                remaining: Uint128::zero()
+            })
+        );
+    }
+
+    #[test]
+    fn only_owner_can_set_limits() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let limits = Limits {
+            max_per_tx: Some(Uint128::new(10)),
+            ..Limits::default()
+        };
+        let attacker = mock_info("Attacker", &[]);
+        let set_limits_msg = ExecuteMsg::SetLimits {
+            limits: limits.clone(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), attacker, set_limits_msg);
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("Attacker")
+            })
+        );
+
+        // a per-account cap needs a window to apply to
+        let set_limits_msg = ExecuteMsg::SetLimits {
+            limits: Limits {
+                max_per_account: Some(Uint128::new(10)),
+                ..Limits::default()
+            },
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), set_limits_msg);
+        assert_eq!(res, Err(ContractError::InvalidLimits {}));
+
+        let set_limits_msg = ExecuteMsg::SetLimits {
+            limits: limits.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), info, set_limits_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetLimits {}).unwrap();
+        let value: LimitsResponse = from_binary(&res).unwrap();
+        assert_eq!(limits, value.limits);
+    }
+
+    #[test]
+    fn paused_exchange_rejects_exchanges() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let set_tokens_msg = ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked("SOBZ"),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), set_tokens_msg).unwrap();
+
+        // only the owner can pause
+        let attacker = mock_info("Attacker", &[]);
+        let res = execute(deps.as_mut(), mock_env(), attacker, ExecuteMsg::Pause {});
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("Attacker")
+            })
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Pause {},
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {}).unwrap();
+        let value: PausedResponse = from_binary(&res).unwrap();
+        assert!(value.paused);
+
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(18),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let execute_msg = ExecuteMsg::Receive(receive_msg);
+        let tree = mock_info("TREE", &[]);
+        let res = execute(deps.as_mut(), mock_env(), tree.clone(), execute_msg.clone());
+        assert_eq!(res, Err(ContractError::Paused {}));
+
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Unpause {}).unwrap();
+        execute(deps.as_mut(), mock_env(), tree, execute_msg).unwrap();
+    }
+
+    #[test]
+    fn migrate_state_from_v0_1() {
+        let mut deps = mock_dependencies(&[]);
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.1.0").unwrap();
+        // State as 0.1 serialized it
+        deps.storage.set(
+            b"state",
+            br#"{"owner":"Dorium","exchanged":"42","value_token_address":"TREE","sobz_token_address":"SOBZ"}"#,
+        );
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!("0.1.0", res.attributes[1].value);
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(CONTRACT_VERSION, version.version);
+
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(Some(Addr::unchecked("Dorium")), state.owner);
+        assert_eq!(Uint128::new(42), state.exchanged);
+        assert_eq!(Limits::default(), state.limits);
+        assert_eq!(Some(Addr::unchecked("TREE")), state.redeem.token);
+        let config = INPUT_TOKENS
+            .load(&deps.storage, &Addr::unchecked("TREE"))
+            .unwrap();
+        assert_eq!(input_token("TREE", one_to_one()).config, config);
+        assert!(!state.paused);
+
+        // and exchanging still works
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(8),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let info = mock_info("TREE", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Receive(receive_msg),
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
+        let value: ExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(50), value.exchanged);
+        let exchanged = INPUT_EXCHANGED
+            .load(&deps.storage, &Addr::unchecked("TREE"))
+            .unwrap();
+        assert_eq!(Uint128::new(50), exchanged);
+    }
+
+    #[test]
+    fn migrate_v0_1_without_tokens() {
+        let mut deps = mock_dependencies(&[]);
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.1.0").unwrap();
+        // SetTokens was never called, so both are still the instantiator
+        deps.storage.set(
+            b"state",
+            br#"{"owner":"Dorium","exchanged":"0","value_token_address":"creator","sobz_token_address":"creator"}"#,
+        );
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(None, state.sobz_token_address);
+        assert_eq!(None, state.redeem.token);
+        let creator = Addr::unchecked("creator");
+        assert_eq!(
+            None,
+            INPUT_TOKENS.may_load(&deps.storage, &creator).unwrap()
+        );
+
+        // so the instantiator can't exchange anything for SoBz
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("creator"),
+            amount: Uint128::new(8),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let info = mock_info("creator", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Receive(receive_msg),
+        );
+        assert_eq!(res, Err(ContractError::TokensNotConfigured {}));
+    }
+
+    #[test]
+    fn migrate_refuses_other_contracts_and_downgrades() {
+        let mut deps = mock_dependencies(&[]);
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+        assert_eq!(
+            res,
+            Err(ContractError::CannotMigrate {
+                previous_contract: String::from("crates.io:cw20-base")
+            })
+        );
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+        assert_eq!(
+            res,
+            Err(ContractError::CannotMigrateVersion {
+                previous_version: String::from("99.0.0")
+            })
+        );
+    }
+
+    #[test]
+    fn ownership_is_transferred_in_two_steps() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            owner: String::from("dorium"),
+            limits: None,
+            input_tokens: vec![],
+            sobz_token_address: None,
+        };
+        let dorium = mock_info("dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), dorium.clone(), msg).unwrap();
+
+        // only the owner can offer ownership
+        let propose = ExecuteMsg::ProposeOwner {
+            new_owner: String::from("dorium-dao"),
+            expires: Some(Expiration::AtHeight(mock_env().block.height + 10)),
+        };
+        let attacker = mock_info("attacker", &[]);
+        let res = execute(deps.as_mut(), mock_env(), attacker.clone(), propose.clone());
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("dorium"),
+                sender: Addr::unchecked("attacker")
+            })
+        );
+        execute(deps.as_mut(), mock_env(), dorium.clone(), propose).unwrap();
+
+        // the owner stays the same until the offer is accepted
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Owner {}).unwrap();
+        let value: OwnerResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Addr::unchecked("dorium")), value.owner);
+        assert_eq!(Some(Addr::unchecked("dorium-dao")), value.pending_owner);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            attacker,
+            ExecuteMsg::AcceptOwnership {},
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::NotPendingOwner {
+                pending_owner: Addr::unchecked("dorium-dao"),
+                sender: Addr::unchecked("attacker")
+            })
+        );
+
+        // the offer runs out
+        let dao = mock_info("dorium-dao", &[]);
+        let mut env = mock_env();
+        env.block.height += 10;
+        let res = execute(
+            deps.as_mut(),
+            env,
+            dao.clone(),
+            ExecuteMsg::AcceptOwnership {},
+        );
+        assert_eq!(res, Err(ContractError::OwnershipTransferExpired {}));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            dao.clone(),
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Owner {}).unwrap();
+        let value: OwnerResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Addr::unchecked("dorium-dao")), value.owner);
+        assert_eq!(None, value.pending_owner);
+
+        // the old owner is no longer in charge
+        let set_limits_msg = ExecuteMsg::SetLimits {
+            limits: Limits::default(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), dorium, set_limits_msg.clone());
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("dorium-dao"),
+                sender: Addr::unchecked("dorium")
+            })
+        );
+
+        // and once renounced, nobody is
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            dao.clone(),
+            ExecuteMsg::RenounceOwnership {},
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), mock_env(), dao, set_limits_msg);
+        assert_eq!(res, Err(ContractError::NoOwner {}));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Owner {}).unwrap();
+        let value: OwnerResponse = from_binary(&res).unwrap();
+        assert_eq!(None, value.owner);
+    }
+
+    #[test]
+    fn instantiate_with_tokens() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("tree", 6), ("sobz", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("tree", one_to_one())],
+            sobz_token_address: Some(String::from("sobz")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // no SetTokens needed
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(18),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        };
+        let info = mock_info("tree", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Receive(receive_msg),
+        )
+        .unwrap();
+        assert_eq!(2, res.messages.len());
+
+        // token addresses must be valid
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("t", one_to_one())],
+            sobz_token_address: Some(String::from("sobz")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    }
+
+    #[test]
+    fn set_tokens_verifies_tokens() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6), ("GOLD", 18)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: None,
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let set_sobz_token = |sobz: &str| ExecuteMsg::SetSobzToken {
+            sobz_token_address: Addr::unchecked(sobz),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            set_sobz_token("Attacker"),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::NotCw20Token {
+                address: Addr::unchecked("Attacker")
+            })
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            set_sobz_token("SOBZ"),
+        )
+        .unwrap();
+
+        // input tokens are checked against SoBz as well
+        let add_input_token = |token: &str| ExecuteMsg::AddInputToken {
+            address: String::from(token),
+            config: input_token(token, one_to_one()).config,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            add_input_token("GOLD"),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::DecimalsMismatch {
+                value_decimals: 18,
+                sobz_decimals: 6
+            })
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            add_input_token("TREE"),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::InputTokenExists {
+                token: Addr::unchecked("TREE")
+            })
+        );
+
+        // we have to be the SoBz minter
+        mock_cw20_tokens(&mut deps.querier, &[("TREE", 6), ("SOBZ", 6)], "Dorium");
+        let res = execute(deps.as_mut(), mock_env(), info, set_sobz_token("SOBZ"));
+        assert_eq!(
+            res,
+            Err(ContractError::NotSobzMinter {
+                minter: Some(String::from("Dorium"))
+            })
+        );
+    }
+
+    #[test]
+    fn redeem_sobz_for_value() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: Some(String::from("SOBZ")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let redeem_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(200),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        });
+        let sobz = mock_info("SOBZ", &[]);
+        let res = execute(deps.as_mut(), mock_env(), sobz.clone(), redeem_msg.clone());
+        assert_eq!(res, Err(ContractError::RedeemDisabled {}));
+
+        // half a Value token per SoBz, keeping back 1%
+        let mut config = RedeemConfig {
+            enabled: true,
+            token: Some(Addr::unchecked("TREE")),
+            rate: Rate {
+                numerator: Uint128::new(1),
+                denominator: Uint128::new(2),
+            },
+            fee_bps: 100,
+            source: RedeemSource::Reserve {},
+        };
+        let set_redeem_msg = ExecuteMsg::SetRedeem {
+            config: config.clone(),
+        };
+        let attacker = mock_info("Attacker", &[]);
+        let res = execute(deps.as_mut(), mock_env(), attacker, set_redeem_msg.clone());
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("Attacker")
+            })
+        );
+        execute(deps.as_mut(), mock_env(), info.clone(), set_redeem_msg).unwrap();
+
+        let res = execute(deps.as_mut(), mock_env(), sobz.clone(), redeem_msg.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::new(200)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("TREE"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: String::from("some user"),
+                    amount: Uint128::new(99)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // minting instead of paying out of the reserve
+        config.source = RedeemSource::Mint {};
+        let set_redeem_msg = ExecuteMsg::SetRedeem { config };
+        execute(deps.as_mut(), mock_env(), info.clone(), set_redeem_msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), sobz, redeem_msg).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("TREE"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("some user"),
+                    amount: Uint128::new(99)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // redemptions are counted apart from exchanges
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRedeem {}).unwrap();
+        let value: RedeemResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(400), value.redeemed);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
+        let value: ExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.exchanged);
+
+        // fees can't be more than everything
+        let set_redeem_msg = ExecuteMsg::SetRedeem {
+            config: RedeemConfig {
+                fee_bps: 10_001,
+                ..RedeemConfig::default()
+            },
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, set_redeem_msg);
+        assert_eq!(res, Err(ContractError::InvalidRedeemConfig {}));
+    }
+
+    #[test]
+    fn exchange_fees_go_to_treasury() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token(
+                "TREE",
+                Rate {
+                    numerator: Uint128::new(2),
+                    denominator: Uint128::new(1),
+                },
+            )],
+            sobz_token_address: Some(String::from("SOBZ")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // 1% of the Value tokens, but at least 5
+        let fee = Fee {
+            bps: 100,
+            min: Some(Uint128::new(5)),
+            treasury: Addr::unchecked("treasury"),
+            taken_from: FeeSource::Value {},
+        };
+        let set_fee_msg = ExecuteMsg::SetFee {
+            fee: Some(fee.clone()),
+        };
+        let attacker = mock_info("Attacker", &[]);
+        let res = execute(deps.as_mut(), mock_env(), attacker, set_fee_msg.clone());
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("Attacker")
+            })
+        );
+        execute(deps.as_mut(), mock_env(), info.clone(), set_fee_msg).unwrap();
+
+        let exchange_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(100),
+            msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+        });
+        let tree = mock_info("TREE", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            tree.clone(),
+            exchange_msg.clone(),
+        )
+        .unwrap();
+        let call = |contract: &str, msg: Cw20ExecuteMsg| {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from(contract),
+                msg: to_binary(&msg).unwrap(),
+                funds: vec![],
+            })
+        };
+        let amount = Uint128::new;
+        assert_eq!(
+            res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>(),
+            vec![
+                call("TREE", Cw20ExecuteMsg::Burn { amount: amount(95) }),
+                call(
+                    "SOBZ",
+                    Cw20ExecuteMsg::Mint {
+                        recipient: String::from("some user"),
+                        amount: amount(190)
+                    }
+                ),
+                call(
+                    "TREE",
+                    Cw20ExecuteMsg::Transfer {
+                        recipient: String::from("treasury"),
+                        amount: amount(5)
+                    }
+                ),
+            ]
+        );
+
+        // now 10% of the SoBz
+        let set_fee_msg = ExecuteMsg::SetFee {
+            fee: Some(Fee {
+                bps: 1_000,
+                min: None,
+                taken_from: FeeSource::Sobz {},
+                ..fee
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), set_fee_msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), tree, exchange_msg).unwrap();
+        assert_eq!(
+            res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>(),
+            vec![
+                call(
+                    "TREE",
+                    Cw20ExecuteMsg::Burn {
+                        amount: amount(100)
+                    }
+                ),
+                call(
+                    "SOBZ",
+                    Cw20ExecuteMsg::Mint {
+                        recipient: String::from("some user"),
+                        amount: amount(180)
+                    }
+                ),
+                call(
+                    "SOBZ",
+                    Cw20ExecuteMsg::Mint {
+                        recipient: String::from("treasury"),
+                        amount: amount(20)
+                    }
+                ),
+            ]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFees {}).unwrap();
+        let value: FeesResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![ValueFees {
+                token: String::from("TREE"),
+                collected: amount(5)
+            }],
+            value.value_collected
+        );
+        assert_eq!(amount(20), value.sobz_collected);
+
+        // the account and its history count what it received, after fees
+        let msg = QueryMsg::GetAccountExchanged {
+            address: String::from("some user"),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AccountExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(amount(370), value.exchanged);
+        let msg = QueryMsg::ExchangeHistory {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExchangeHistoryResponse = from_binary(&res).unwrap();
+        let amounts_out: Vec<_> = value.events.iter().map(|e| e.amount_out).collect();
+        assert_eq!(vec![amount(190), amount(180)], amounts_out);
+
+        // no more than everything
+        let set_fee_msg = ExecuteMsg::SetFee {
+            fee: Some(Fee {
+                bps: 10_001,
+                min: None,
+                treasury: Addr::unchecked("treasury"),
+                taken_from: FeeSource::Sobz {},
+            }),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, set_fee_msg);
+        assert_eq!(res, Err(ContractError::InvalidFee {}));
+    }
+
+    #[test]
+    fn exchange_to_recipient_with_min_out() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: Some(String::from("SOBZ")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let exchange = |deps: DepsMut, payload: Binary| {
+            let receive_msg = Cw20ReceiveMsg {
+                sender: String::from("some user"),
+                amount: Uint128::new(18),
+                msg: payload,
+            };
+            let info = mock_info("TREE", &[]);
+            execute(deps, mock_env(), info, ExecuteMsg::Receive(receive_msg))
+        };
+
+        // the payload has to make sense
+        let res = exchange(deps.as_mut(), Binary::from(b"{\"burn\":{}}".to_vec()));
+        assert!(matches!(res, Err(ContractError::InvalidReceiveMsg { .. })));
+
+        // we won't take less than asked for
+        let payload = to_binary(&ReceiveMsg::Exchange {
+            recipient: None,
+            min_out: Some(Uint128::new(20)),
+        })
+        .unwrap();
+        let res = exchange(deps.as_mut(), payload);
+        assert_eq!(
+            res,
+            Err(ContractError::MinOutNotMet {
+                received: Uint128::new(18),
+                min_out: Uint128::new(20)
+            })
+        );
+
+        // SoBz can be minted to someone else
+        let payload = to_binary(&ReceiveMsg::Exchange {
+            recipient: Some(String::from("friend")),
+            min_out: Some(Uint128::new(18)),
+        })
+        .unwrap();
+        let res = exchange(deps.as_mut(), payload).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("friend"),
+                    amount: Uint128::new(18)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // but it still counts as the sender's exchange
+        let msg = QueryMsg::GetAccountExchanged {
+            address: String::from("some user"),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AccountExchangedResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(18), value.exchanged);
+        let msg = QueryMsg::ExchangeHistory {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExchangeHistoryResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("friend"), value.events[0].recipient);
+    }
+
+    #[test]
+    fn exchange_into_proposal() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: Some(String::from("SOBZ")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let receive_msg = Cw20ReceiveMsg {
+            sender: String::from("some user"),
+            amount: Uint128::new(18),
+            msg: to_binary(&ReceiveMsg::FundProposal {
+                proposal: String::from("proposals"),
+                id: String::from("foobar"),
+                min_out: None,
+            })
+            .unwrap(),
+        };
+        let info = mock_info("TREE", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Receive(receive_msg),
+        )
+        .unwrap();
+
+        // the SoBz are minted to us, then sent to the proposal contract
+        assert_eq!(3, res.messages.len());
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from(MOCK_CONTRACT_ADDR),
+                    amount: Uint128::new(18)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        // naming who exchanged, so the proposal refunds them and not us
+        let top_up =
+            Binary::from(br#"{"top_up":{"id":"foobar","contributor":"some user"}}"#.to_vec());
+        assert_eq!(
+            res.messages[2].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: String::from("proposals"),
+                    amount: Uint128::new(18),
+                    msg: top_up,
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn exchange_multiple_input_tokens() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(
+            &mut deps.querier,
+            &[("TREE", 6), ("RIVER", 6), ("SOBZ", 6)],
+            MOCK_CONTRACT_ADDR,
+        );
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: None,
+            input_tokens: vec![input_token("TREE", one_to_one())],
+            sobz_token_address: Some(String::from("SOBZ")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // RIVER is worth 2 SoBz, but only 10 per exchange and 15 in total
+        let mut river = input_token(
+            "RIVER",
+            Rate {
+                numerator: Uint128::new(2),
+                denominator: Uint128::new(1),
+            },
+        );
+        river.config.max_per_tx = Some(Uint128::new(10));
+        river.config.max_total = Some(Uint128::new(15));
+        let add_msg = ExecuteMsg::AddInputToken {
+            address: river.address.clone(),
+            config: river.config.clone(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("Attacker", &[]),
+            add_msg.clone(),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
                 owner: Addr::unchecked("Dorium"),
                 sender: Addr::unchecked("Attacker")
             })
         );
+        execute(deps.as_mut(), mock_env(), info.clone(), add_msg).unwrap();
+
+        let exchange = |deps: DepsMut, token: &str, amount: u128| {
+            let receive_msg = Cw20ReceiveMsg {
+                sender: String::from("some user"),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::Send {}).unwrap(),
+            };
+            execute(
+                deps,
+                mock_env(),
+                mock_info(token, &[]),
+                ExecuteMsg::Receive(receive_msg),
+            )
+        };
+        let res = exchange(deps.as_mut(), "RIVER", 8).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("some user"),
+                    amount: Uint128::new(16)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        exchange(deps.as_mut(), "TREE", 8).unwrap();
+        let res = exchange(deps.as_mut(), "RIVER", 11);
+        assert_eq!(
+            res,
+            Err(ContractError::InputTxLimitExceeded {
+                amount: Uint128::new(11),
+                max: Uint128::new(10)
+            })
+        );
+        let res = exchange(deps.as_mut(), "RIVER", 8);
+        assert_eq!(
+            res,
+            Err(ContractError::InputCapExceeded {
+                amount: Uint128::new(8),
+                remaining: Uint128::new(7)
+            })
+        );
+
+        // disabled tokens stay listed, removed ones don't
+        let mut tree = input_token("TREE", one_to_one());
+        tree.config.enabled = false;
+        let update_msg = ExecuteMsg::UpdateInputToken {
+            address: tree.address,
+            config: tree.config,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), update_msg).unwrap();
+        let res = exchange(deps.as_mut(), "TREE", 8);
+        assert_eq!(
+            res,
+            Err(ContractError::InputTokenDisabled {
+                token: Addr::unchecked("TREE")
+            })
+        );
+
+        let msg = QueryMsg::ListInputTokens {
+            start_after: None,
+            limit: Some(1),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: InputTokensResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.tokens.len());
+        assert_eq!(Addr::unchecked("RIVER"), value.tokens[0].address);
+        assert_eq!(Uint128::new(8), value.tokens[0].exchanged);
+        let msg = QueryMsg::ListInputTokens {
+            start_after: Some(String::from("RIVER")),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: InputTokensResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("TREE"), value.tokens[0].address);
+        assert!(!value.tokens[0].config.enabled);
+
+        let remove_msg = ExecuteMsg::RemoveInputToken {
+            address: String::from("RIVER"),
+        };
+        execute(deps.as_mut(), mock_env(), info, remove_msg).unwrap();
+        let res = exchange(deps.as_mut(), "RIVER", 1);
+        assert_eq!(
+            res,
+            Err(ContractError::UnauthorizedValueToken {
+                info_sender: Addr::unchecked("RIVER")
+            })
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetExchanged {}).unwrap();
+        let value: ExchangedResponse = from_binary(&res).unwrap();
+        // counted in SoBz minted, not input tokens
+        assert_eq!(Uint128::new(24), value.exchanged);
+    }
+
+    #[test]
+    fn exchange_native_coins() {
+        let mut deps = mock_dependencies(&[]);
+        mock_cw20_tokens(&mut deps.querier, &[("SOBZ", 6)], MOCK_CONTRACT_ADDR);
+
+        let msg = InstantiateMsg {
+            owner: String::from("Dorium"),
+            limits: Some(Limits {
+                max_per_tx: None,
+                max_per_account: None,
+                account_window: 0,
+                daily_cap: Some(Uint128::new(20)),
+            }),
+            input_tokens: vec![],
+            sobz_token_address: Some(String::from("SOBZ")),
+        };
+        let info = mock_info("Dorium", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let exchange_msg = ExecuteMsg::ExchangeNative {};
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "udor")),
+            exchange_msg.clone(),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::UnknownDenom {
+                denom: String::from("udor")
+            })
+        );
+
+        // 3 SoBz for every 2 udor, forwarded to the treasury
+        let set_rate_msg = ExecuteMsg::SetNativeRate {
+            denom: String::from("udor"),
+            rate: Some(Rate {
+                numerator: Uint128::new(3),
+                denominator: Uint128::new(2),
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), set_rate_msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            exchange_msg.clone(),
+        );
+        assert_eq!(res, Err(ContractError::InvalidNativeFunds {}));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "udor")),
+            exchange_msg.clone(),
+        )
+        .unwrap();
+        // held by the exchange for now
+        assert_eq!(1, res.messages.len());
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: String::from("SOBZ"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("alice"),
+                    amount: Uint128::new(15)
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // only the owner can take out the coins held
+        let withdraw_msg = ExecuteMsg::WithdrawNative {
+            denom: String::from("udor"),
+            amount: Uint128::new(10),
+            recipient: String::from("treasury"),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            withdraw_msg.clone(),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::Unauthorized {
+                owner: Addr::unchecked("Dorium"),
+                sender: Addr::unchecked("alice")
+            })
+        );
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), withdraw_msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: String::from("treasury"),
+                amount: coins(10, "udor"),
+            })
+        );
+
+        let set_treasury_msg = ExecuteMsg::SetNativeTreasury {
+            treasury: Some(String::from("treasury")),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), set_treasury_msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(2, "udor")),
+            exchange_msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: String::from("treasury"),
+                amount: coins(2, "udor"),
+            })
+        );
+
+        // the daily cap is shared with CW20 exchanges
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(4, "udor")),
+            exchange_msg.clone(),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::DailyCapExceeded {
+                minted: Uint128::new(6),
+                remaining: Uint128::new(2)
+            })
+        );
+
+        let msg = QueryMsg::GetAccountExchanged {
+            address: String::from("alice"),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AccountExchangedResponse = from_binary(&res).unwrap();
+        // 15 + 3 SoBz minted for 12 udor
+        assert_eq!(Uint128::new(18), value.exchanged);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetNative {}).unwrap();
+        let value: NativeResponse = from_binary(&res).unwrap();
+        assert_eq!(String::from("udor"), value.rates[0].denom);
+        assert_eq!(Some(Addr::unchecked("treasury")), value.treasury);
+
+        // Value fees are counted per denom
+        let set_fee_msg = ExecuteMsg::SetFee {
+            fee: Some(Fee {
+                bps: 5_000,
+                min: None,
+                treasury: Addr::unchecked("treasury"),
+                taken_from: FeeSource::Value {},
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), info, set_fee_msg).unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(2, "udor")),
+            exchange_msg,
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFees {}).unwrap();
+        let value: FeesResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![ValueFees {
+                token: String::from("udor"),
+                collected: Uint128::new(1)
+            }],
+            value.value_collected
+        );
+        assert_eq!(Uint128::zero(), value.sobz_collected);
     }
 }
@@ -1,4 +1,4 @@
-use cosmwasm_std::{Addr, StdError};
+use cosmwasm_std::{Addr, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -6,14 +6,109 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
-    #[error("Unauthorized: info.sender {info_sender:?} is NOT state.value_token_address {state_value_token_address:?}")]
-    UnauthorizedValueToken {
-        info_sender: Addr,
-        state_value_token_address: Addr,
+    #[error("Unauthorized: info.sender {info_sender:?} is NOT a whitelisted input token")]
+    UnauthorizedValueToken { info_sender: Addr },
+
+    #[error("The SoBz token address has not been set yet")]
+    TokensNotConfigured {},
+
+    #[error("{token} is already an input token")]
+    InputTokenExists { token: Addr },
+
+    #[error("{token} is not an input token")]
+    InputTokenNotFound { token: Addr },
+
+    #[error("Exchanging {token} is disabled")]
+    InputTokenDisabled { token: Addr },
+
+    #[error("Send exactly one native coin to exchange")]
+    InvalidNativeFunds {},
+
+    #[error("No rate has been set for {denom}")]
+    UnknownDenom { denom: String },
+
+    #[error("At most {max} of this input token can be exchanged per transaction, not {amount}")]
+    InputTxLimitExceeded { amount: Uint128, max: Uint128 },
+
+    #[error("Only {remaining} more of this input token can be exchanged, not {amount}")]
+    InputCapExceeded { amount: Uint128, remaining: Uint128 },
+
+    #[error("{address} is not a CW20 token contract")]
+    NotCw20Token { address: Addr },
+
+    #[error("The exchange contract must be the SoBz minter, but the minter is {minter:?}")]
+    NotSobzMinter { minter: Option<String> },
+
+    #[error("The rate assumes Value and SoBz have the same decimals, but they have {value_decimals} and {sobz_decimals}")]
+    DecimalsMismatch {
+        value_decimals: u8,
+        sobz_decimals: u8,
     },
 
     #[error("Unauthorized Set Token: {sender:?} tried to modify the CW20 Token Addresses but {owner:?} owns the exchange contract")]
     UnauthorizedSetToken { owner: Addr, sender: Addr },
+
+    #[error("Unauthorized: {sender:?} is not the owner {owner:?} of the exchange contract")]
+    Unauthorized { owner: Addr, sender: Addr },
+
+    #[error("The exchange contract has no owner anymore")]
+    NoOwner {},
+
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner {},
+
+    #[error("Unauthorized: {sender:?} is not the pending owner {pending_owner:?}")]
+    NotPendingOwner { pending_owner: Addr, sender: Addr },
+
+    #[error("The ownership transfer has expired")]
+    OwnershipTransferExpired {},
+
+    #[error("Invalid rate: numerator and denominator must both be greater than 0")]
+    InvalidRate {},
+
+    #[error("Cannot decode the Cw20ReceiveMsg payload: {error}")]
+    InvalidReceiveMsg { error: String },
+
+    #[error("Exchange would mint {received} SoBz, fewer than the {min_out} asked for")]
+    MinOutNotMet { received: Uint128, min_out: Uint128 },
+
+    #[error("Exchanging {amount} Value tokens would mint 0 SoBz")]
+    ExchangeTooSmall { amount: Uint128 },
+
+    #[error("Invalid fee: it can be at most 10000 basis points")]
+    InvalidFee {},
+
+    #[error("Redeeming SoBz for Value tokens is disabled")]
+    RedeemDisabled {},
+
+    #[error(
+        "Invalid redeem config: the rate must be valid, the fee at most 10000 basis points and a token set to enable it"
+    )]
+    InvalidRedeemConfig {},
+
+    #[error("Redeeming {amount} SoBz would pay out 0 Value tokens")]
+    RedeemTooSmall { amount: Uint128 },
+
+    #[error("The exchange is paused")]
+    Paused {},
+
+    #[error("Cannot migrate from a different contract: {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from version {previous_version}")]
+    CannotMigrateVersion { previous_version: String },
+
+    #[error("Invalid limits: account_window must be greater than 0 when max_per_account is set")]
+    InvalidLimits {},
+
+    #[error("Exchange would mint {minted} SoBz but at most {max} can be minted per transaction")]
+    TxLimitExceeded { minted: Uint128, max: Uint128 },
+
+    #[error("Exchange would mint {minted} SoBz but this account can only mint {remaining} more in the current window")]
+    AccountLimitExceeded { minted: Uint128, remaining: Uint128 },
+
+    #[error("Exchange would mint {minted} SoBz but only {remaining} more can be minted today")]
+    DailyCapExceeded { minted: Uint128, remaining: Uint128 },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
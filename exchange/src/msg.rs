@@ -1,21 +1,109 @@
 use cosmwasm_std::{Addr, Uint128};
+use cw0::Expiration;
 use cw20::Cw20ReceiveMsg;
+
+use crate::state::{ExchangeEvent, Fee, Limits, Rate, RedeemConfig, TokenConfig};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
-    pub owner: Addr,
+    pub owner: String,
+    // caps on how many SoBz can be minted, unlimited if not given
+    pub limits: Option<Limits>,
+    // the Value tokens that can be exchanged, more can be added with AddInputToken
+    pub input_tokens: Vec<InputToken>,
+    // the CW20 token to mint. If not given, it has to be set with
+    // SetSobzToken before anything can be exchanged
+    pub sobz_token_address: Option<String>,
 }
 
+// InputToken whitelists a CW20 Value token to be exchanged into SoBz
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InputToken {
+    pub address: String,
+    pub config: TokenConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Receive(Cw20ReceiveMsg),
-    SetTokens {
-        value_token_address: Addr,
+    // ExchangeNative mints SoBz to the sender for the single native coin sent
+    // along, at the rate set for its denom
+    ExchangeNative {},
+    SetSobzToken {
         sobz_token_address: Addr,
     },
+    // AddInputToken whitelists another Value token to be exchanged into SoBz.
+    // Only the owner can do this
+    AddInputToken {
+        address: String,
+        config: TokenConfig,
+    },
+    // UpdateInputToken changes the rate, caps or enabled flag of an input token.
+    // Only the owner can do this
+    UpdateInputToken {
+        address: String,
+        config: TokenConfig,
+    },
+    // SetRate changes how many SoBz are minted per unit of an input token. Only
+    // the owner can do this
+    SetRate {
+        token: String,
+        rate: Rate,
+    },
+    // RemoveInputToken takes a Value token off the whitelist. Only the owner can do this
+    RemoveInputToken {
+        address: String,
+    },
+    // SetNativeRate sets how many SoBz are minted per native coin of denom, or
+    // stops exchanging it if None. Only the owner can do this
+    SetNativeRate {
+        denom: String,
+        rate: Option<Rate>,
+    },
+    // SetNativeTreasury forwards native coins exchanged to treasury, or keeps
+    // them in the exchange if None. Only the owner can do this
+    SetNativeTreasury {
+        treasury: Option<String>,
+    },
+    // WithdrawNative sends native coins held by the exchange to recipient.
+    // Only the owner can do this
+    WithdrawNative {
+        denom: String,
+        amount: Uint128,
+        recipient: String,
+    },
+    // SetLimits replaces the caps on how many SoBz can be minted. Only the owner can do this
+    SetLimits {
+        limits: Limits,
+    },
+    // SetFee sets the fee taken on every exchange, or removes it if None.
+    // Only the owner can do this
+    SetFee {
+        fee: Option<Fee>,
+    },
+    // SetRedeem configures exchanging SoBz back into Value tokens, which
+    // happens when the SoBz token sends us a Cw20ReceiveMsg. Only the owner can do this
+    SetRedeem {
+        config: RedeemConfig,
+    },
+    // Pause stops all exchanges until Unpause is called. Only the owner can do this
+    Pause {},
+    Unpause {},
+    // ProposeOwner offers ownership to new_owner, replacing any earlier offer.
+    // Nothing changes until they accept it with AcceptOwnership
+    ProposeOwner {
+        new_owner: String,
+        expires: Option<Expiration>,
+    },
+    AcceptOwnership {},
+    // RenounceOwnership leaves the contract without an owner, for good
+    RenounceOwnership {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -24,13 +112,72 @@ pub enum ReceiveMsg {
     // we can already get the recipient from the info.sender, so we really don't
     // need to say anything more here
     Send {},
+    // Exchange mints the SoBz to recipient, or to whoever sent the Value tokens
+    // if not given, and fails if that would be fewer than min_out
+    Exchange {
+        recipient: Option<String>,
+        min_out: Option<Uint128>,
+    },
+    // FundProposal sends the SoBz on to top up escrow id in the proposal
+    // contract, so exchanging and funding happen in one transaction
+    FundProposal {
+        proposal: String,
+        id: String,
+        min_out: Option<Uint128>,
+    },
+}
+
+// ProposalReceiveMsg is the part of the proposal contract's ReceiveMsg we send
+// along with SoBz, see ReceiveMsg::FundProposal
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalReceiveMsg {
+    // contributor is who exchanged, so refunds go to them and not to us
+    TopUp {
+        id: String,
+        contributor: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetExchanged returns the amount of value token exchanged for sobz as a json-encoded number
+    // GetExchanged returns the amount of sobz received for everything exchanged as a json-encoded number
     GetExchanged {},
+    // GetRate returns how many SoBz are minted per unit of an input token
+    GetRate {
+        token: String,
+    },
+    // ListInputTokens returns the whitelisted Value tokens, ordered by address
+    ListInputTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // GetNative returns the rate per native denom and where the coins go
+    GetNative {},
+    // GetFees returns the fee and how much has been sent to the treasury so far
+    GetFees {},
+    // GetRedeem returns the redeem config and how many SoBz have been redeemed
+    GetRedeem {},
+    // GetAccountExchanged returns the amount of sobz received for a single account's exchanges
+    GetAccountExchanged {
+        address: String,
+    },
+    // Owner returns the current owner and whoever ownership has been offered to
+    Owner {},
+    // GetPaused returns whether exchanges are currently paused
+    GetPaused {},
+    // GetLimits returns the configured minting caps
+    GetLimits {},
+    // GetAllowance returns how many SoBz an account can still mint under each cap
+    GetAllowance {
+        address: String,
+    },
+    // ExchangeHistory lists past exchanges, oldest first
+    ExchangeHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
 // We define a custom struct for each query response
@@ -38,3 +185,94 @@ pub enum QueryMsg {
 pub struct ExchangedResponse {
     pub exchanged: Uint128,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RateResponse {
+    pub rate: Rate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InputTokensResponse {
+    pub tokens: Vec<InputTokenInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InputTokenInfo {
+    pub address: Addr,
+    pub config: TokenConfig,
+    // how much of this token has been exchanged so far
+    pub exchanged: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NativeResponse {
+    pub rates: Vec<NativeRate>,
+    pub treasury: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NativeRate {
+    pub denom: String,
+    pub rate: Rate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeesResponse {
+    pub fee: Option<Fee>,
+    // Value fees sent to the treasury, per input token or native denom
+    pub value_collected: Vec<ValueFees>,
+    // SoBz minted to the treasury as fees
+    pub sobz_collected: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValueFees {
+    // input token address or native denom
+    pub token: String,
+    pub collected: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RedeemResponse {
+    pub config: RedeemConfig,
+    pub redeemed: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AccountExchangedResponse {
+    pub address: String,
+    pub exchanged: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExchangeHistoryResponse {
+    pub events: Vec<ExchangeEvent>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnerResponse {
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<Addr>,
+    pub pending_expires: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PausedResponse {
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LimitsResponse {
+    pub limits: Limits,
+}
+
+// None means the corresponding cap is not set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowanceResponse {
+    pub address: String,
+    pub per_tx: Option<Uint128>,
+    pub account: Option<Uint128>,
+    pub daily: Option<Uint128>,
+    // the smallest of the above, i.e. the most SoBz the next exchange can mint
+    pub remaining: Option<Uint128>,
+}
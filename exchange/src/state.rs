@@ -1,15 +1,334 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Timestamp, Uint128};
+use cw0::Expiration;
+use cw20::Denom;
+use cw_storage_plus::{Item, Map, U64Key};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
+    // None once ownership has been renounced
+    pub owner: Option<Addr>,
+    // SoBz received for all input tokens and native coins together, after
+    // fees, so amounts of tokens with different rates and decimals aren't mixed up
+    pub exchanged: Uint128,
+    // None until it is configured, see ExecuteMsg::SetSobzToken
+    pub sobz_token_address: Option<Addr>,
+    pub limits: Limits,
+    // while paused no exchanges can be made
+    pub paused: bool,
+    // SoBz exchanged back into Value tokens, see RedeemConfig
+    pub redeemed: Uint128,
+    pub redeem: RedeemConfig,
+    // taken on every exchange, see Fee
+    pub fee: Option<Fee>,
+    // SoBz minted to the treasury as fees, Value fees are in VALUE_FEES
+    pub sobz_fees_collected: Uint128,
+    // native coins exchanged are forwarded here, or held by the exchange if None
+    pub native_treasury: Option<Addr>,
+}
+
+/// Rate is how many SoBz are minted per input token, as a fraction. When
+/// redeeming, it is how many Value tokens are paid per SoBz
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Rate {
+    pub numerator: Uint128,
+    pub denominator: Uint128,
+}
+
+impl Rate {
+    pub fn is_valid(&self) -> bool {
+        !self.numerator.is_zero() && !self.denominator.is_zero()
+    }
+
+    /// amount * numerator / denominator, rounded down
+    pub fn apply(&self, amount: Uint128) -> Uint128 {
+        amount.multiply_ratio(self.numerator, self.denominator)
+    }
+}
+
+/// TokenConfig is how a whitelisted Value token is exchanged into SoBz
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenConfig {
+    pub rate: Rate,
+    // most of this token a single exchange can take, and all exchanges
+    // together. None means unlimited
+    pub max_per_tx: Option<Uint128>,
+    pub max_total: Option<Uint128>,
+    // disabled tokens stay whitelisted but can't be exchanged
+    pub enabled: bool,
+}
+
+impl TokenConfig {
+    pub fn is_valid(&self) -> bool {
+        self.rate.is_valid()
+    }
+}
+
+/// Limits caps how many SoBz can be minted. A limit of None means unlimited
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct Limits {
+    // most SoBz a single exchange can mint
+    pub max_per_tx: Option<Uint128>,
+    // most SoBz a single account can mint within any account_window seconds
+    pub max_per_account: Option<Uint128>,
+    pub account_window: u64,
+    // most SoBz all accounts together can mint within any 24 hours
+    pub daily_cap: Option<Uint128>,
+}
+
+impl Limits {
+    pub fn is_valid(&self) -> bool {
+        self.max_per_account.is_none() || self.account_window > 0
+    }
+}
+
+/// Fee is taken on every Value to SoBz exchange and sent to the treasury
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Fee {
+    // in basis points of the Value tokens or SoBz, see taken_from
+    pub bps: u16,
+    // the fee is at least this much, unless the whole amount is less
+    pub min: Option<Uint128>,
+    pub treasury: Addr,
+    pub taken_from: FeeSource,
+}
+
+impl Fee {
+    pub fn is_valid(&self) -> bool {
+        self.bps <= 10_000
+    }
+
+    /// the fee on amount, rounded down and capped at amount
+    pub fn apply(&self, amount: Uint128) -> Uint128 {
+        let fee = amount.multiply_ratio(self.bps, 10_000u128);
+        fee.max(self.min.unwrap_or_default()).min(amount)
+    }
+}
+
+/// FeeSource is which token the fee is taken in
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeSource {
+    // part of the Value tokens is sent to the treasury instead of being burned
+    Value {},
+    // part of the SoBz is minted to the treasury instead of the account
+    Sobz {},
+}
+
+/// RedeemConfig governs exchanging SoBz back into Value tokens
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RedeemConfig {
+    pub enabled: bool,
+    // the Value token paid out, has to be set to enable redemption
+    pub token: Option<Addr>,
+    pub rate: Rate,
+    // part of the Value tokens kept back on every redemption, in basis points
+    pub fee_bps: u16,
+    pub source: RedeemSource,
+}
+
+impl Default for RedeemConfig {
+    fn default() -> Self {
+        RedeemConfig {
+            enabled: false,
+            token: None,
+            rate: Rate {
+                numerator: Uint128::new(1),
+                denominator: Uint128::new(1),
+            },
+            fee_bps: 0,
+            source: RedeemSource::Reserve {},
+        }
+    }
+}
+
+impl RedeemConfig {
+    pub fn is_valid(&self) -> bool {
+        self.rate.is_valid() && self.fee_bps <= 10_000 && (self.token.is_some() || !self.enabled)
+    }
+
+    /// the fee kept back from amount Value tokens, rounded down
+    pub fn fee(&self, amount: Uint128) -> Uint128 {
+        amount.multiply_ratio(self.fee_bps, 10_000u128)
+    }
+}
+
+/// RedeemSource is where the Value tokens paid out on redemption come from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RedeemSource {
+    // the exchange mints them, so it has to be the Value token minter
+    Mint {},
+    // the exchange transfers them from its own balance
+    Reserve {},
+}
+
+// how many buckets a Window is split into
+const WINDOW_BUCKETS: u64 = 24;
+
+/// Window counts the SoBz minted in the last `length` seconds, rolling along
+/// with time. Mints are grouped into buckets of 1/WINDOW_BUCKETS of the
+/// length, and a bucket counts until all of it has left the window, so the
+/// count is never less than what was actually minted in the window
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct Window {
+    pub buckets: Vec<WindowBucket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WindowBucket {
+    // mints up to here are in this bucket
+    pub end: Timestamp,
+    pub minted: Uint128,
+}
+
+impl Window {
+    /// SoBz minted in the window that ends at now
+    pub fn minted_at(&self, now: Timestamp, length: u64) -> Uint128 {
+        let since = now.seconds().saturating_sub(length);
+        self.buckets
+            .iter()
+            .filter(|b| b.end.seconds() > since)
+            .map(|b| b.minted)
+            .sum()
+    }
+
+    /// adds amount to the window at now, dropping buckets that have left it
+    pub fn record(window: Option<Window>, now: Timestamp, length: u64, amount: Uint128) -> Window {
+        let since = now.seconds().saturating_sub(length);
+        let mut buckets: Vec<_> = window
+            .unwrap_or_default()
+            .buckets
+            .into_iter()
+            .filter(|b| b.end.seconds() > since)
+            .collect();
+        match buckets.last_mut() {
+            Some(last) if last.end.seconds() > now.seconds() => last.minted += amount,
+            _ => {
+                let size = (length / WINDOW_BUCKETS).max(1);
+                let end = (now.seconds() / size)
+                    .saturating_add(1)
+                    .saturating_mul(size);
+                buckets.push(WindowBucket {
+                    // from_seconds would overflow for ends past what a
+                    // Timestamp holds, those are clamped to its last second
+                    end: Timestamp::from_nanos(end.saturating_mul(1_000_000_000)),
+                    minted: amount,
+                });
+            }
+        }
+        Window { buckets }
+    }
+}
+
+pub const STATE: Item<State> = Item::new("state");
+
+/// PendingOwner is who ownership has been offered to, until they accept it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingOwner {
+    pub owner: Addr,
+    pub expires: Option<Expiration>,
+}
+
+pub const PENDING_OWNER: Item<PendingOwner> = Item::new("pending_owner");
+
+// State as stored by version 0.1 of this contract, before the rate, limits and
+// pause were added and when there was a single Value token. Only read when
+// migrating
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyState {
     pub owner: Addr,
     pub exchanged: Uint128,
     pub value_token_address: Addr,
     pub sobz_token_address: Addr,
 }
 
-pub const STATE: Item<State> = Item::new("state");
+impl From<LegacyState> for State {
+    fn from(state: LegacyState) -> State {
+        let tokens_set = state.tokens_set();
+        State {
+            owner: Some(state.owner),
+            exchanged: state.exchanged,
+            sobz_token_address: Some(state.sobz_token_address).filter(|_| tokens_set),
+            limits: Limits::default(),
+            paused: false,
+            redeemed: Uint128::zero(),
+            // the Value token itself becomes an input token, see
+            // LegacyState::input_token
+            redeem: RedeemConfig {
+                token: Some(state.value_token_address).filter(|_| tokens_set),
+                ..RedeemConfig::default()
+            },
+            fee: None,
+            sobz_fees_collected: Uint128::zero(),
+            native_treasury: None,
+        }
+    }
+}
+
+impl LegacyState {
+    // 0.1 stored its instantiator as both tokens until SetTokens was called,
+    // that placeholder isn't a token
+    fn tokens_set(&self) -> bool {
+        self.value_token_address != self.sobz_token_address
+    }
+
+    /// 0.1 minted one SoBz per Value token, without any limits. None if the
+    /// tokens were never set
+    pub fn input_token(&self) -> Option<(Addr, TokenConfig)> {
+        if !self.tokens_set() {
+            return None;
+        }
+        let config = TokenConfig {
+            rate: Rate {
+                numerator: Uint128::new(1),
+                denominator: Uint128::new(1),
+            },
+            max_per_tx: None,
+            max_total: None,
+            enabled: true,
+        };
+        Some((self.value_token_address.clone(), config))
+    }
+}
+
+pub const LEGACY_STATE: Item<LegacyState> = Item::new("state");
+// the Value tokens that can be exchanged into SoBz, keyed by token contract
+pub const INPUT_TOKENS: Map<&Addr, TokenConfig> = Map::new("input_tokens");
+// total of each input token exchanged so far, see TokenConfig.max_total
+pub const INPUT_EXCHANGED: Map<&Addr, Uint128> = Map::new("input_exchanged");
+// SoBz minted per native coin, keyed by denom, see ExecuteMsg::ExchangeNative
+pub const NATIVE_RATES: Map<&str, Rate> = Map::new("native_rates");
+// Value fees sent to the treasury, keyed by input token address or native
+// denom, as amounts of different tokens can't be added up
+pub const VALUE_FEES: Map<&str, Uint128> = Map::new("value_fees");
+// SoBz minted per account in its rolling window, see Limits.max_per_account
+pub const ACCOUNT_WINDOWS: Map<&Addr, Window> = Map::new("account_windows");
+// SoBz minted by everyone in the last 24 hours, see Limits.daily_cap
+pub const DAILY_WINDOW: Item<Window> = Item::new("daily_window");
+
+/// ExchangeEvent records a single Value to SoBz exchange
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExchangeEvent {
+    pub id: u64,
+    pub height: u64,
+    pub time: Timestamp,
+    // which input token or native coin was exchanged
+    pub token: Denom,
+    // who sent the Value tokens
+    pub account: Addr,
+    // who received the SoBz
+    pub recipient: Addr,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+}
+
+// total SoBz received for each account's exchanges, after fees, the sum of
+// ExchangeEvent.amount_out
+pub const ACCOUNT_EXCHANGED: Map<&Addr, Uint128> = Map::new("account_exchanged");
+// append-only log of every exchange, keyed by ExchangeEvent.id
+pub const EXCHANGE_HISTORY: Map<U64Key, ExchangeEvent> = Map::new("exchange_history");
+// number of exchanges so far, the id of the last ExchangeEvent
+pub const EXCHANGE_COUNT: Item<u64> = Item::new("exchange_count");
@@ -4,7 +4,8 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use proposal::msg::{
-    CreateMsg, DetailsResponse, ExecuteMsg, InstantiateMsg, ListResponse, QueryMsg, ReceiveMsg,
+    BallotResponse, ConfigResponse, ContributionsResponse, CreateMsg, DetailsResponse, ExecuteMsg,
+    InstantiateMsg, ListResponse, MigrateMsg, QueryMsg, ReceiveMsg,
 };
 use proposal::state::Escrow;
 
@@ -16,8 +17,12 @@ fn main() {
     export_schema(&schema_for!(CreateMsg), &out_dir);
     export_schema(&schema_for!(DetailsResponse), &out_dir);
     export_schema(&schema_for!(ListResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(BallotResponse), &out_dir);
+    export_schema(&schema_for!(ContributionsResponse), &out_dir);
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(ReceiveMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(Escrow), &out_dir);
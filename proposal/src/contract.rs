@@ -1,15 +1,23 @@
-use crate::state::{all_escrow_details, all_escrow_ids, Escrow, GenericBalance, ESCROWS};
+use crate::state::{
+    escrows, list_contributions, list_escrows, Ballot, CommunityVote, Config, Escrow,
+    GenericBalance, Milestone, MilestoneAmount, MilestoneStatus, PendingValidatorChange, Tally,
+    ValidatorChange, ValidatorChangeRecord, Vote, BALLOTS, CONFIG, CONTRIBUTIONS, LEGACY_ESCROWS,
+    PAUSED,
+};
 use cosmwasm_std::{
-    attr, entry_point, from_binary, to_binary, Addr, BankMsg, Binary, CosmosMsg, Deps, DepsMut,
-    Env, MessageInfo, Response, StdError, StdResult, WasmMsg,
+    attr, entry_point, from_binary, to_binary, Addr, Api, Attribute, BankMsg, Binary, CosmosMsg,
+    Decimal, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Storage, Uint128,
+    WasmMsg,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use cw20::{Balance, Cw20Coin, Cw20CoinVerified, Cw20ExecuteMsg, Cw20ReceiveMsg};
 
 use crate::error::ContractError;
 use crate::msg::{
-    CreateMsg, DetailsResponse, ExecuteMsg, InstantiateMsg, ListDetailedResponse, ListResponse,
-    QueryMsg, ReceiveMsg,
+    is_valid_name, is_valid_url, BallotResponse, CommunityVoteMsg, ConfigResponse,
+    ContributionResponse, ContributionsResponse, CreateMsg, DetailsResponse, EscrowFilter,
+    ExecuteMsg, InstantiateMsg, ListDetailedResponse, ListResponse, MigrateMsg, MilestoneAmountMsg,
+    MilestoneMsg, MilestoneResponse, QueryMsg, ReceiveMsg, MAX_DESCRIPTION_LENGTH, MAX_URL_LENGTH,
 };
 use crate::state::Status;
 
@@ -17,16 +25,30 @@ use crate::state::Status;
 const CONTRACT_NAME: &str = "dorium-community-proposal";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// pagination info for the list queries
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: InstantiateMsg,
-) -> StdResult<Response> {
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    // no setup
-    Ok(Response::default())
+
+    let admin = match &msg.admin {
+        Some(admin) => deps.api.addr_validate(admin)?,
+        None => info.sender,
+    };
+    let config = build_config(deps.api, admin, msg)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "instantiate"),
+        attr("admin", config.admin),
+    ]))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -36,38 +58,188 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // refunds, unlocking votes and admin messages still work while paused
+    match msg {
+        ExecuteMsg::Refund { .. }
+        | ExecuteMsg::ClaimRefund { .. }
+        | ExecuteMsg::Unlock { .. }
+        | ExecuteMsg::UpdateConfig { .. }
+        | ExecuteMsg::Pause {}
+        | ExecuteMsg::Unpause {} => {}
+        _ => {
+            if PAUSED.may_load(deps.storage)?.unwrap_or_default() {
+                return Err(ContractError::Paused {});
+            }
+        }
+    }
+
     match msg {
         ExecuteMsg::Create(msg) => {
-            execute_create(deps, msg, Balance::from(info.funds), &info.sender)
+            execute_create(deps, env, msg, Balance::from(info.funds), &info.sender)
         }
+        ExecuteMsg::Accept { id } => execute_accept(deps, env, info, id),
+        ExecuteMsg::Reopen { id } => execute_reopen(deps, info, id),
         ExecuteMsg::Approve { id } => execute_approve(deps, env, info, id),
-        ExecuteMsg::TopUp { id } => execute_top_up(deps, id, Balance::from(info.funds)),
+        ExecuteMsg::TopUp { id, contributor } => execute_top_up(
+            deps,
+            env,
+            id,
+            Balance::from(info.funds),
+            &info.sender,
+            contributor,
+        ),
         ExecuteMsg::Refund { id } => execute_refund(deps, env, info, id),
-        ExecuteMsg::Receive(msg) => execute_receive(deps, info, msg),
+        ExecuteMsg::ClaimRefund { id } => execute_claim_refund(deps, info, id),
+        ExecuteMsg::ApproveMilestone { id, milestone } => {
+            execute_approve_milestone(deps, env, info, id, milestone)
+        }
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::Unlock { id } => execute_unlock(deps, env, info, id),
+        ExecuteMsg::AddValidator { id, validator } => {
+            let change = ValidatorChange::Add {
+                validator: deps.api.addr_validate(&validator)?,
+            };
+            execute_change_validators(deps, env, info, id, change)
+        }
+        ExecuteMsg::RemoveValidator { id, validator } => {
+            let change = ValidatorChange::Remove {
+                validator: deps.api.addr_validate(&validator)?,
+            };
+            execute_change_validators(deps, env, info, id, change)
+        }
+        ExecuteMsg::ReplaceValidator { id, old, new } => {
+            let change = ValidatorChange::Replace {
+                old: deps.api.addr_validate(&old)?,
+                new: deps.api.addr_validate(&new)?,
+            };
+            execute_change_validators(deps, env, info, id, change)
+        }
+        ExecuteMsg::UpdateConfig {
+            admin,
+            validators,
+            proposal_creators,
+            cw20_whitelist,
+            min_funding,
+            max_funding,
+            exchange,
+        } => {
+            let config = CONFIG.load(deps.storage)?;
+            if info.sender != config.admin {
+                return Err(ContractError::Unauthorized {});
+            }
+            let admin = match admin {
+                Some(admin) => deps.api.addr_validate(&admin)?,
+                None => config.admin,
+            };
+            // whatever isn't given stays as it is
+            let current = query_config(deps.as_ref())?;
+            let msg = InstantiateMsg {
+                admin: None,
+                validators: Some(validators.unwrap_or(current.validators)),
+                proposal_creators: proposal_creators.unwrap_or(current.proposal_creators),
+                cw20_whitelist: Some(cw20_whitelist.unwrap_or(current.cw20_whitelist)),
+                min_funding: min_funding.unwrap_or(current.min_funding),
+                max_funding: max_funding.unwrap_or(current.max_funding),
+                exchange: exchange.unwrap_or(current.exchange),
+            };
+            let config = build_config(deps.api, admin, msg)?;
+            CONFIG.save(deps.storage, &config)?;
+            Ok(Response::new().add_attributes(vec![
+                attr("action", "update_config"),
+                attr("admin", config.admin),
+            ]))
+        }
+        ExecuteMsg::Pause {} => execute_set_paused(deps, info, true),
+        ExecuteMsg::Unpause {} => execute_set_paused(deps, info, false),
+    }
+}
+
+fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    PAUSED.save(deps.storage, &paused)?;
+    let action = if paused { "pause" } else { "unpause" };
+    Ok(Response::new().add_attribute("action", action))
+}
+
+/// Validates the config settings, `msg.admin` is ignored in favor of `admin`
+fn build_config(api: &dyn Api, admin: Addr, msg: InstantiateMsg) -> Result<Config, ContractError> {
+    if let (Some(min), Some(max)) = (msg.min_funding, msg.max_funding) {
+        if min > max {
+            return Err(ContractError::InvalidFundingLimits {});
+        }
     }
+    Ok(Config {
+        admin,
+        validators: validate_addrs(api, &msg.validators.unwrap_or_default())?,
+        proposal_creators: match msg.proposal_creators {
+            Some(creators) => Some(validate_addrs(api, &creators)?),
+            None => None,
+        },
+        cw20_whitelist: validate_addrs(api, &msg.cw20_whitelist.unwrap_or_default())?,
+        min_funding: msg.min_funding,
+        max_funding: msg.max_funding,
+        exchange: match msg.exchange {
+            Some(exchange) => Some(api.addr_validate(&exchange)?),
+            None => None,
+        },
+    })
+}
+
+fn validate_addrs(api: &dyn Api, addrs: &[String]) -> StdResult<Vec<Addr>> {
+    addrs.iter().map(|a| api.addr_validate(a)).collect()
 }
 
 pub fn execute_receive(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     wrapper: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
     let msg: ReceiveMsg = from_binary(&wrapper.msg)?;
     let balance = Balance::Cw20(Cw20CoinVerified {
-        address: info.sender,
+        address: info.sender.clone(),
         amount: wrapper.amount,
     });
     let api = deps.api;
     match msg {
-        ReceiveMsg::Create(msg) => {
-            execute_create(deps, msg, balance, &api.addr_validate(&wrapper.sender)?)
-        }
-        ReceiveMsg::TopUp { id } => execute_top_up(deps, id, balance),
+        ReceiveMsg::Create(msg) => execute_create(
+            deps,
+            env,
+            *msg,
+            balance,
+            &api.addr_validate(&wrapper.sender)?,
+        ),
+        ReceiveMsg::TopUp { id, contributor } => execute_top_up(
+            deps,
+            env,
+            id,
+            balance,
+            &api.addr_validate(&wrapper.sender)?,
+            contributor,
+        ),
+        // votes lock the tokens, they don't fund the escrow
+        ReceiveMsg::Vote { id, vote } => execute_vote(
+            deps,
+            env,
+            id,
+            api.addr_validate(&wrapper.sender)?,
+            vote,
+            info.sender,
+            wrapper.amount,
+        ),
     }
 }
 
 pub fn execute_create(
     deps: DepsMut,
+    env: Env,
     msg: CreateMsg,
     balance: Balance,
     sender: &Addr,
@@ -75,8 +247,36 @@ pub fn execute_create(
     if balance.is_empty() {
         return Err(ContractError::EmptyBalance {});
     }
+    check_metadata(&msg)?;
+    let config = CONFIG.load(deps.storage)?;
+    // Dorium may restrict who can open proposals
+    if let Some(creators) = &config.proposal_creators {
+        if !creators.contains(sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+    }
+    if let Some(min) = config.min_funding {
+        let enough = match &balance {
+            Balance::Native(balance) => balance.0.iter().all(|c| c.amount >= min),
+            Balance::Cw20(token) => token.amount >= min,
+        };
+        if !enough {
+            return Err(ContractError::BelowMinFunding { min });
+        }
+    }
+    // no point in creating an escrow that can only be refunded
+    if let Some(expires) = &msg.expires {
+        if expires.is_expired(&env.block) {
+            return Err(ContractError::Expired {});
+        }
+    }
 
     let mut cw20_whitelist = msg.addr_whitelist(deps.api)?;
+    for token in &config.cw20_whitelist {
+        if !cw20_whitelist.contains(token) {
+            cw20_whitelist.push(token.clone())
+        }
+    }
 
     let escrow_balance = match balance {
         Balance::Native(balance) => GenericBalance {
@@ -97,26 +297,71 @@ pub fn execute_create(
 
     let mut validators: Vec<Addr> = vec![];
     for addr in msg.validators {
-        validators.push(deps.api.addr_validate(&addr)?)
+        let validator = deps.api.addr_validate(&addr)?;
+        if validators.contains(&validator) {
+            return Err(ContractError::DuplicateValidator { validator: addr });
+        }
+        validators.push(validator)
+    }
+    if validators.is_empty() {
+        validators = config.validators.clone();
+    }
+    if validators.is_empty() {
+        return Err(ContractError::NoValidators {});
+    }
+    let proposer = deps.api.addr_validate(&msg.proposer)?;
+    let source = match &msg.source {
+        Some(source) => deps.api.addr_validate(source)?,
+        None => sender.clone(),
+    };
+    // nobody gets to approve their own work
+    if validators.contains(&proposer) {
+        return Err(ContractError::ProposerIsValidator {});
     }
 
+    // a quorum of validators decides, so the threshold must be reachable
+    if msg.threshold == 0 || msg.threshold as usize > validators.len() {
+        return Err(ContractError::InvalidThreshold {});
+    }
+
+    let milestones = match msg.milestones {
+        Some(milestones) => milestones_from_msg(deps.api, milestones)?,
+        None => vec![],
+    };
+    let community_vote = match msg.community_vote {
+        Some(vote) => Some(community_vote_from_msg(deps.api, vote)?),
+        None => None,
+    };
+
     let escrow = Escrow {
         id: msg.id.clone(),
         url: msg.url.clone(),
         description: msg.description,
-        validators: validators,
-        proposer: deps.api.addr_validate(&msg.proposer)?,
-        source: sender.clone(),
+        validators,
+        threshold: msg.threshold,
+        approvals: vec![],
+        refunds: vec![],
+        proposer,
+        source,
         balance: escrow_balance,
         cw20_whitelist,
         status: Status::Opened {},
+        milestones,
+        expires: msg.expires,
+        community_vote,
+        pending_validator_changes: vec![],
+        validator_history: vec![],
     };
+    check_max_funding(&config, &escrow.balance)?;
+    // what the escrow is created with is refunded to the source
+    let contribution = (escrow.source.clone(), escrow.balance.clone());
 
     // try to store it, fail if the id was already in use
-    ESCROWS.update(deps.storage, &msg.id, |existing| match existing {
+    escrows().update(deps.storage, &msg.id, |existing| match existing {
         None => Ok(escrow),
         Some(_) => Err(ContractError::AlreadyInUse {}),
     })?;
+    CONTRIBUTIONS.save(deps.storage, (&msg.id, &contribution.0), &contribution.1)?;
 
     let res = Response::new().add_attributes(vec![attr("action", "create"), attr("id", msg.id)]);
     Ok(res)
@@ -124,18 +369,32 @@ pub fn execute_create(
 
 pub fn execute_top_up(
     deps: DepsMut,
+    env: Env,
     id: String,
     balance: Balance,
+    sender: &Addr,
+    contributor: Option<String>,
 ) -> Result<Response, ContractError> {
     if balance.is_empty() {
         return Err(ContractError::EmptyBalance {});
     }
+    let config = CONFIG.load(deps.storage)?;
+    // the exchange mints to itself first, so it says who actually paid
+    let contributor = match contributor {
+        Some(contributor) if config.exchange.as_ref() == Some(sender) => {
+            deps.api.addr_validate(&contributor)?
+        }
+        Some(_) => return Err(ContractError::Unauthorized {}),
+        None => sender.clone(),
+    };
     // this fails is no escrow there
-    let mut escrow = ESCROWS.load(deps.storage, &id)?;
+    let mut escrow = escrows().load(deps.storage, &id)?;
 
     // If status is Completed or Canceled, don't let people send tokens to this escrow anymore!
     if escrow.locked() {
         return Err(ContractError::Locked {});
+    } else if escrow.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
     }
 
     if let Balance::Cw20(token) = &balance {
@@ -145,14 +404,109 @@ pub fn execute_top_up(
         }
     };
 
-    escrow.balance.add_tokens(balance);
-
-    // and save
-    ESCROWS.save(deps.storage, &id, &escrow)?;
+    escrow.balance.add_tokens(balance.clone());
+    check_max_funding(&config, &escrow.balance)?;
+
+    // and save, remembering who to refund
+    escrows().save(deps.storage, &id, &escrow)?;
+    CONTRIBUTIONS.update(
+        deps.storage,
+        (&id, &contributor),
+        |contribution| -> StdResult<_> {
+            let mut contribution = contribution.unwrap_or_default();
+            contribution.add_tokens(balance);
+            Ok(contribution)
+        },
+    )?;
     let res = Response::new().add_attributes(vec![attr("action", "top_up"), attr("id", id)]);
     Ok(res)
 }
 
+/// Makes sure the id, url and description can be stored and shown as they are
+fn check_metadata(msg: &CreateMsg) -> Result<(), ContractError> {
+    if !is_valid_name(&msg.id) {
+        return Err(ContractError::InvalidId {});
+    } else if !is_valid_url(&msg.url) {
+        return Err(ContractError::InvalidUrl {});
+    } else if msg.url.len() > MAX_URL_LENGTH {
+        return Err(ContractError::UrlTooLong {
+            max: MAX_URL_LENGTH,
+        });
+    } else if msg.description.len() > MAX_DESCRIPTION_LENGTH {
+        return Err(ContractError::DescriptionTooLong {
+            max: MAX_DESCRIPTION_LENGTH,
+        });
+    }
+    Ok(())
+}
+
+/// Makes sure the escrow doesn't hold more of any token than the config allows
+fn check_max_funding(config: &Config, balance: &GenericBalance) -> Result<(), ContractError> {
+    if let Some(max) = config.max_funding {
+        let too_much = balance.native.iter().any(|c| c.amount > max)
+            || balance.cw20.iter().any(|c| c.amount > max);
+        if too_much {
+            return Err(ContractError::AboveMaxFunding { max });
+        }
+    }
+    Ok(())
+}
+
+pub fn execute_accept(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: String,
+) -> Result<Response, ContractError> {
+    // this fails is no escrow there
+    let mut escrow = escrows().load(deps.storage, &id)?;
+
+    // only the proposer can commit to doing the work
+    if info.sender != escrow.proposer {
+        return Err(ContractError::Unauthorized {});
+    } else if escrow.locked() {
+        return Err(ContractError::Locked {});
+    } else if escrow.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+    let transition = transition(&mut escrow, Status::InProgress {})?;
+
+    escrows().save(deps.storage, &id, &escrow)?;
+
+    let mut attributes = vec![attr("action", "accept"), attr("id", id)];
+    attributes.extend(transition);
+    Ok(Response::new().add_attributes(attributes))
+}
+
+pub fn execute_reopen(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: String,
+) -> Result<Response, ContractError> {
+    // this fails is no escrow there
+    let mut escrow = escrows().load(deps.storage, &id)?;
+
+    if !escrow.validators.contains(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    } else if escrow.locked() {
+        return Err(ContractError::Locked {});
+    }
+    let transition = transition(&mut escrow, Status::Opened {})?;
+    // votes were cast on the work done so far, start over
+    escrow.approvals = vec![];
+    for milestone in escrow.milestones.iter_mut() {
+        if milestone.status == (MilestoneStatus::Pending {}) {
+            milestone.approvals = vec![];
+        }
+    }
+
+    escrows().save(deps.storage, &id, &escrow)?;
+
+    let mut attributes = vec![attr("action", "reopen"), attr("id", id)];
+    attributes.extend(transition);
+    Ok(Response::new().add_attributes(attributes))
+}
+
 pub fn execute_approve(
     deps: DepsMut,
     env: Env,
@@ -160,27 +514,57 @@ pub fn execute_approve(
     id: String,
 ) -> Result<Response, ContractError> {
     // this fails is no escrow there
-    let mut escrow = ESCROWS.load(deps.storage, &id)?;
+    let mut escrow = escrows().load(deps.storage, &id)?;
 
     if !escrow.validators.contains(&info.sender) {
         return Err(ContractError::Unauthorized {});
     } else if escrow.locked() {
         return Err(ContractError::Locked {});
+    } else if escrow.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    } else if !escrow.status.can_transition_to(&Status::Completed {}) {
+        return Err(ContractError::InvalidTransition {
+            from: escrow.status,
+            to: Status::Completed {},
+        });
+    }
+    // a validator can't count towards both quorums
+    if escrow.refunds.contains(&info.sender) {
+        return Err(ContractError::VotedOtherWay {});
     }
-    {
-        escrow.status = Status::Completed {};
+    let votes = add_vote(&mut escrow.approvals, &info.sender)?;
+    if votes < escrow.threshold {
+        // not enough validators agree yet, just remember this vote
+        escrows().save(deps.storage, &id, &escrow)?;
+
+        let attributes = vec![
+            attr("action", "approve"),
+            attr("id", id),
+            attr("votes", votes.to_string()),
+            attr("threshold", escrow.threshold.to_string()),
+        ];
+        Ok(Response::new().add_attributes(attributes))
+    } else {
+        // the token holders have to agree as well
+        if let Some(vote) = &escrow.community_vote {
+            if !vote.passed() {
+                return Err(ContractError::CommunityVoteNotPassed {});
+            }
+        }
+        let transition = transition(&mut escrow, Status::Completed {})?;
 
         // send all tokens out
         let messages = send_tokens(&escrow.proposer, &escrow.balance)?;
 
         // save the updated status field
-        ESCROWS.save(deps.storage, &id, &escrow)?;
+        escrows().save(deps.storage, &id, &escrow)?;
 
-        let attributes = vec![
+        let mut attributes = vec![
             attr("action", "approve"),
             attr("id", id),
             attr("to", escrow.proposer),
         ];
+        attributes.extend(transition);
         Ok(Response::new()
             .add_attributes(attributes)
             .add_messages(messages))
@@ -194,31 +578,484 @@ pub fn execute_refund(
     id: String,
 ) -> Result<Response, ContractError> {
     // this fails is no escrow there
-    let mut escrow = ESCROWS.load(deps.storage, &id)?;
+    let mut escrow = escrows().load(deps.storage, &id)?;
+
+    if escrow.locked() {
+        return Err(ContractError::Locked {});
+    }
+
+    // only a validator can decide to refund the escrowed funds (to DORIUM), but
+    // once the escrow has expired anyone can, so the funds never get stuck
+    let expired = escrow.is_expired(&env.block);
+    if !expired {
+        if !escrow.validators.contains(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if escrow.approvals.contains(&info.sender) {
+            return Err(ContractError::VotedOtherWay {});
+        }
+        let votes = add_vote(&mut escrow.refunds, &info.sender)?;
+        if votes < escrow.threshold {
+            // not enough validators agree yet, just remember this vote
+            escrows().save(deps.storage, &id, &escrow)?;
+
+            let attributes = vec![
+                attr("action", "refund"),
+                attr("id", id),
+                attr("votes", votes.to_string()),
+                attr("threshold", escrow.threshold.to_string()),
+            ];
+            return Ok(Response::new().add_attributes(attributes));
+        }
+    }
+
+    // the tokens stay here until each contributor claims them, so one
+    // refund never has to pay everyone at once
+    let transition = transition(&mut escrow, Status::Canceled {})?;
+
+    // save the updated status field
+    escrows().save(deps.storage, &id, &escrow)?;
+
+    let mut attributes = vec![attr("action", "refund"), attr("id", id)];
+    attributes.extend(transition);
+    Ok(Response::new().add_attributes(attributes))
+}
+
+pub fn execute_claim_refund(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: String,
+) -> Result<Response, ContractError> {
+    // this fails is no escrow there
+    let escrow = escrows().load(deps.storage, &id)?;
+
+    if escrow.status != (Status::Canceled {}) {
+        return Err(ContractError::NotCanceled {});
+    }
+    let key = (id.as_str(), &info.sender);
+    let contributed = CONTRIBUTIONS
+        .may_load(deps.storage, key)?
+        .ok_or(ContractError::NothingToClaim {})?;
+    // so it can only be claimed once
+    CONTRIBUTIONS.remove(deps.storage, key);
+
+    let refund = refund_share(&escrow, &contributed);
+    let messages = send_tokens(&info.sender, &refund)?;
+
+    let attributes = vec![
+        attr("action", "claim_refund"),
+        attr("id", id),
+        attr("to", info.sender),
+    ];
+    Ok(Response::new()
+        .add_attributes(attributes)
+        .add_messages(messages))
+}
+
+pub fn execute_vote(
+    deps: DepsMut,
+    env: Env,
+    id: String,
+    voter: Addr,
+    vote: Vote,
+    token: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    // this fails is no escrow there
+    let mut escrow = escrows().load(deps.storage, &id)?;
+
+    if escrow.locked() {
+        return Err(ContractError::Locked {});
+    } else if escrow.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+    let community_vote = escrow
+        .community_vote
+        .as_mut()
+        .ok_or(ContractError::NoCommunityVote {})?;
+    if token != community_vote.token {
+        return Err(ContractError::WrongVoteToken {
+            token: community_vote.token.to_string(),
+        });
+    }
+    // one vote per holder, so nobody can vote both ways
+    let key = (id.as_str(), &voter);
+    if BALLOTS.has(deps.storage, key) {
+        return Err(ContractError::AlreadyVoted {});
+    }
+    community_vote.tally.add(&vote, amount);
+    BALLOTS.save(
+        deps.storage,
+        key,
+        &Ballot {
+            vote,
+            weight: amount,
+        },
+    )?;
+    let passed = community_vote.passed();
+
+    escrows().save(deps.storage, &id, &escrow)?;
+
+    let attributes = vec![
+        attr("action", "vote"),
+        attr("id", id),
+        attr("voter", voter),
+        attr("weight", amount),
+        attr("passed", passed.to_string()),
+    ];
+    Ok(Response::new().add_attributes(attributes))
+}
+
+pub fn execute_unlock(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: String,
+) -> Result<Response, ContractError> {
+    // this fails is no escrow there
+    let escrow = escrows().load(deps.storage, &id)?;
+    let community_vote = escrow
+        .community_vote
+        .as_ref()
+        .ok_or(ContractError::NoCommunityVote {})?;
+
+    // the tally stays as it was, the tokens just go back
+    if !escrow.locked() && !escrow.is_expired(&env.block) {
+        return Err(ContractError::VoteStillOpen {});
+    }
+    let key = (id.as_str(), &info.sender);
+    let ballot = BALLOTS.load(deps.storage, key)?;
+    BALLOTS.remove(deps.storage, key);
+
+    let message = WasmMsg::Execute {
+        contract_addr: community_vote.token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: info.sender.to_string(),
+            amount: ballot.weight,
+        })?,
+        funds: vec![],
+    };
+    let attributes = vec![
+        attr("action", "unlock"),
+        attr("id", id),
+        attr("voter", info.sender),
+        attr("weight", ballot.weight),
+    ];
+    Ok(Response::new()
+        .add_attributes(attributes)
+        .add_message(message))
+}
+
+pub fn execute_change_validators(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: String,
+    change: ValidatorChange,
+) -> Result<Response, ContractError> {
+    // this fails is no escrow there
+    let mut escrow = escrows().load(deps.storage, &id)?;
+
+    if escrow.locked() {
+        return Err(ContractError::Locked {});
+    }
+    check_validator_change(&escrow, &change)?;
+    let action = match change {
+        ValidatorChange::Add { .. } => "add_validator",
+        ValidatorChange::Remove { .. } => "remove_validator",
+        ValidatorChange::Replace { .. } => "replace_validator",
+    };
+
+    // the admin can change validators right away, the validators need a quorum
+    let approved_by = if info.sender == CONFIG.load(deps.storage)?.admin {
+        vec![info.sender]
+    } else if escrow.validators.contains(&info.sender) {
+        let pending = &mut escrow.pending_validator_changes;
+        let index = match pending.iter().position(|p| p.change == change) {
+            Some(index) => index,
+            None => {
+                pending.push(PendingValidatorChange {
+                    change: change.clone(),
+                    approvals: vec![],
+                });
+                pending.len() - 1
+            }
+        };
+        let votes = add_vote(&mut pending[index].approvals, &info.sender)?;
+        if votes < escrow.threshold {
+            // not enough validators agree yet, just remember this vote
+            escrows().save(deps.storage, &id, &escrow)?;
+
+            let attributes = vec![
+                attr("action", action),
+                attr("id", id),
+                attr("votes", votes.to_string()),
+                attr("threshold", escrow.threshold.to_string()),
+            ];
+            return Ok(Response::new().add_attributes(attributes));
+        }
+        pending.remove(index).approvals
+    } else {
+        return Err(ContractError::Unauthorized {});
+    };
+
+    let mut attributes = vec![attr("action", action), attr("id", id.clone())];
+    if let Some(removed) = change.removed() {
+        escrow.validators.retain(|v| v != removed);
+        // a validator's votes leave with it
+        escrow.approvals.retain(|v| v != removed);
+        escrow.refunds.retain(|v| v != removed);
+        for milestone in escrow.milestones.iter_mut() {
+            milestone.approvals.retain(|v| v != removed);
+        }
+        attributes.push(attr("removed", removed));
+    }
+    if let Some(added) = change.added() {
+        escrow.validators.push(added.clone());
+        attributes.push(attr("added", added));
+    }
+    // votes on other changes were cast with the old validators, start over
+    escrow.pending_validator_changes = vec![];
+    escrow.validator_history.push(ValidatorChangeRecord {
+        change,
+        approved_by,
+        height: env.block.height,
+    });
+
+    // saving also moves the escrow in the validator index
+    escrows().save(deps.storage, &id, &escrow)?;
+    Ok(Response::new().add_attributes(attributes))
+}
+
+/// Makes sure a validator change makes sense for the escrow's current validators
+fn check_validator_change(escrow: &Escrow, change: &ValidatorChange) -> Result<(), ContractError> {
+    let mut validators = escrow.validators.len();
+    if let Some(removed) = change.removed() {
+        if !escrow.validators.contains(removed) {
+            return Err(ContractError::ValidatorNotFound {});
+        }
+        validators -= 1;
+    }
+    if let Some(added) = change.added() {
+        if escrow.validators.contains(added) {
+            return Err(ContractError::ValidatorExists {});
+        } else if added == &escrow.proposer {
+            return Err(ContractError::ProposerIsValidator {});
+        }
+        validators += 1;
+    }
+    // a quorum of validators decides, so the threshold must stay reachable
+    if validators < escrow.threshold as usize {
+        return Err(ContractError::BelowThreshold {});
+    }
+    Ok(())
+}
+
+pub fn execute_approve_milestone(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: String,
+    milestone: u32,
+) -> Result<Response, ContractError> {
+    // this fails is no escrow there
+    let mut escrow = escrows().load(deps.storage, &id)?;
 
-    // only a validator can decide to refund the escrowed funds (to DORIUM)
     if !escrow.validators.contains(&info.sender) {
         return Err(ContractError::Unauthorized {});
     } else if escrow.locked() {
         return Err(ContractError::Locked {});
-    } else {
-        escrow.status = Status::Canceled {};
+    } else if escrow.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    } else if escrow.status != (Status::InProgress {}) {
+        return Err(ContractError::NotInProgress {});
+    }
 
-        // send all tokens out
-        let messages = refund_or_burn_tokens(&escrow.source, &escrow.balance)?;
+    let threshold = escrow.threshold;
+    let stage = escrow
+        .milestones
+        .get_mut(milestone as usize)
+        .ok_or(ContractError::MilestoneNotFound {})?;
+    if stage.status == (MilestoneStatus::Paid {}) {
+        return Err(ContractError::MilestoneAlreadyPaid {});
+    }
+    if let Some(deadline) = &stage.deadline {
+        if deadline.is_expired(&env.block) {
+            return Err(ContractError::MilestoneExpired {});
+        }
+    }
 
-        // save the updated status field
-        ESCROWS.save(deps.storage, &id, &escrow)?;
+    let votes = add_vote(&mut stage.approvals, &info.sender)?;
+    if votes < threshold {
+        // not enough validators agree yet, just remember this vote
+        escrows().save(deps.storage, &id, &escrow)?;
 
         let attributes = vec![
-            attr("action", "refund"),
+            attr("action", "approve_milestone"),
             attr("id", id),
-            attr("to", escrow.source),
+            attr("milestone", milestone.to_string()),
+            attr("votes", votes.to_string()),
+            attr("threshold", threshold.to_string()),
         ];
-        Ok(Response::new()
-            .add_attributes(attributes)
-            .add_messages(messages))
+        return Ok(Response::new().add_attributes(attributes));
+    }
+
+    // the token holders have to agree before anything is paid out
+    if let Some(vote) = &escrow.community_vote {
+        if !vote.passed() {
+            return Err(ContractError::CommunityVoteNotPassed {});
+        }
+    }
+    let payout = milestone_payout(&escrow, milestone as usize)?;
+    escrow
+        .balance
+        .sub_balance(&payout)
+        .map_err(|_| ContractError::InsufficientFunds {})?;
+    let stage = &mut escrow.milestones[milestone as usize];
+    stage.paid = payout.clone();
+    stage.status = MilestoneStatus::Paid {};
+
+    // once every milestone has been paid out and nothing is left, we're done
+    let all_paid = escrow
+        .milestones
+        .iter()
+        .all(|m| m.status == MilestoneStatus::Paid {});
+    let mut transition_attrs = vec![];
+    if all_paid && escrow.balance.native.is_empty() && escrow.balance.cw20.is_empty() {
+        transition_attrs = transition(&mut escrow, Status::Completed {})?;
+    }
+
+    // send this milestone's share out
+    let messages = send_tokens(&escrow.proposer, &payout)?;
+
+    // save the updated balance and milestone
+    escrows().save(deps.storage, &id, &escrow)?;
+
+    let mut attributes = vec![
+        attr("action", "approve_milestone"),
+        attr("id", id),
+        attr("milestone", milestone.to_string()),
+        attr("to", escrow.proposer),
+    ];
+    attributes.extend(transition_attrs);
+    Ok(Response::new()
+        .add_attributes(attributes)
+        .add_messages(messages))
+}
+
+/// Works out what a milestone releases out of the escrow's current balance
+fn milestone_payout(escrow: &Escrow, milestone: usize) -> Result<GenericBalance, ContractError> {
+    match &escrow.milestones[milestone].amount {
+        MilestoneAmount::Share(share) => {
+            // a share of everything received, i.e. what's left plus what's been paid out
+            let mut received = escrow.balance.clone();
+            received.add_balance(&escrow.milestones_paid());
+            Ok(received.share(*share).capped_at(&escrow.balance))
+        }
+        MilestoneAmount::Fixed(amount) => Ok(amount.clone()),
+    }
+}
+
+/// Works out what a contributor gets back out of the escrow's balance: what
+/// they put in, scaled down by what milestones have paid out
+fn refund_share(escrow: &Escrow, contributed: &GenericBalance) -> GenericBalance {
+    let mut received = escrow.balance.clone();
+    received.add_balance(&escrow.milestones_paid());
+    contributed.scaled(&escrow.balance, &received)
+}
+
+fn milestones_from_msg(
+    api: &dyn Api,
+    milestones: Vec<MilestoneMsg>,
+) -> Result<Vec<Milestone>, ContractError> {
+    let mut total_share = Decimal::zero();
+    let mut ans: Vec<Milestone> = vec![];
+    for milestone in milestones {
+        let amount = match milestone.amount {
+            MilestoneAmountMsg::Share(share) => {
+                total_share = total_share + share;
+                MilestoneAmount::Share(share)
+            }
+            MilestoneAmountMsg::Fixed { native, cw20 } => {
+                // transfers of nothing fail, so the milestone could never be paid
+                let empty = native.is_empty() && cw20.is_empty();
+                let zero = native.iter().any(|c| c.amount.is_zero())
+                    || cw20.iter().any(|c| c.amount.is_zero());
+                if empty || zero {
+                    return Err(ContractError::EmptyMilestone {});
+                }
+                let cw20: StdResult<Vec<_>> = cw20
+                    .into_iter()
+                    .map(|c| {
+                        Ok(Cw20CoinVerified {
+                            address: api.addr_validate(&c.address)?,
+                            amount: c.amount,
+                        })
+                    })
+                    .collect();
+                MilestoneAmount::Fixed(GenericBalance {
+                    native,
+                    cw20: cw20?,
+                })
+            }
+        };
+        ans.push(Milestone {
+            title: milestone.title,
+            amount,
+            deadline: milestone.deadline,
+            approvals: vec![],
+            paid: GenericBalance::default(),
+            status: MilestoneStatus::Pending {},
+        });
+    }
+    // shares are of the whole escrow, so together they can't be more than all of it
+    if total_share > Decimal::one() {
+        return Err(ContractError::InvalidMilestones {});
+    }
+    Ok(ans)
+}
+
+fn community_vote_from_msg(
+    api: &dyn Api,
+    msg: CommunityVoteMsg,
+) -> Result<CommunityVote, ContractError> {
+    if msg.threshold.is_zero() || msg.threshold > Decimal::one() {
+        return Err(ContractError::InvalidCommunityVote {});
     }
+    Ok(CommunityVote {
+        token: api.addr_validate(&msg.token)?,
+        quorum: msg.quorum,
+        threshold: msg.threshold,
+        tally: Tally::default(),
+    })
+}
+
+/// Moves the escrow to a new status. All status changes go through here, so
+/// that the lifecycle is enforced in one place and indexers can follow it
+/// through the returned attributes.
+fn transition(escrow: &mut Escrow, to: Status) -> Result<Vec<Attribute>, ContractError> {
+    if !escrow.status.can_transition_to(&to) {
+        return Err(ContractError::InvalidTransition {
+            from: escrow.status.clone(),
+            to,
+        });
+    }
+    let attributes = vec![
+        attr("from_status", escrow.status.to_string()),
+        attr("to_status", to.to_string()),
+    ];
+    escrow.status = to;
+    Ok(attributes)
+}
+
+/// Records a validator's vote and returns how many votes have been cast so far.
+/// Each validator can only vote once.
+fn add_vote(votes: &mut Vec<Addr>, validator: &Addr) -> Result<u32, ContractError> {
+    if votes.contains(validator) {
+        return Err(ContractError::AlreadyVoted {});
+    }
+    votes.push(validator.clone());
+    Ok(votes.len() as u32)
 }
 
 fn send_tokens(to: &Addr, balance: &GenericBalance) -> StdResult<Vec<CosmosMsg>> {
@@ -253,52 +1090,25 @@ fn send_tokens(to: &Addr, balance: &GenericBalance) -> StdResult<Vec<CosmosMsg>>
     Ok(msgs)
 }
 
-fn refund_or_burn_tokens(to: &Addr, balance: &GenericBalance) -> StdResult<Vec<CosmosMsg>> {
-    let native_balance = &balance.native;
-    let mut msgs: Vec<CosmosMsg> = if native_balance.is_empty() {
-        vec![]
-    } else {
-        vec![BankMsg::Send {
-            to_address: to.into(),
-            amount: native_balance.to_vec(),
-        }
-        .into()]
-    };
-
-    let cw20_balance = &balance.cw20;
-    let cw20_msgs: StdResult<Vec<_>> = cw20_balance
-        .iter()
-        .map(|c| {
-            let msg = Cw20ExecuteMsg::Burn { amount: c.amount };
-            let exec = WasmMsg::Execute {
-                contract_addr: c.address.to_string(),
-                msg: to_binary(&msg)?,
-                funds: vec![],
-            };
-            Ok(exec.into())
-        })
-        .collect();
-    msgs.append(&mut cw20_msgs?);
-    Ok(msgs)
-}
-
 impl DetailsResponse {
     fn from_escrow(escrow: &Escrow) -> StdResult<DetailsResponse> {
         let cw20_whitelist = escrow.human_whitelist();
         let validators_str = escrow.human_validators();
         // transform tokens
         let native_balance = escrow.balance.native.clone();
-
-        let cw20_balance: StdResult<Vec<_>> = escrow
-            .balance
-            .cw20
-            .clone()
-            .into_iter()
-            .map(|token| {
-                Ok(Cw20Coin {
-                    address: token.address.into(),
-                    amount: token.amount,
-                })
+        let cw20_balance = cw20_coins(&escrow.balance);
+
+        let milestones = escrow
+            .milestones
+            .iter()
+            .map(|m| MilestoneResponse {
+                title: m.title.clone(),
+                amount: m.amount.clone(),
+                deadline: m.deadline,
+                approvals: m.approvals.iter().map(|a| a.to_string()).collect(),
+                native_paid: m.paid.native.clone(),
+                cw20_paid: cw20_coins(&m.paid),
+                status: m.status.clone(),
             })
             .collect();
 
@@ -307,39 +1117,205 @@ impl DetailsResponse {
             url: escrow.url.clone(),
             description: escrow.description.clone(),
             validators: validators_str,
+            threshold: escrow.threshold,
+            approvals: escrow.approvals.iter().map(|a| a.to_string()).collect(),
+            refunds: escrow.refunds.iter().map(|a| a.to_string()).collect(),
             proposer: escrow.proposer.to_string(),
             source: escrow.source.to_string(),
             native_balance: native_balance.to_vec(),
-            cw20_balance: cw20_balance?,
-            cw20_whitelist: cw20_whitelist,
+            cw20_balance,
+            cw20_whitelist,
             status: escrow.status.clone(),
+            milestones,
+            expires: escrow.expires,
+            community_vote: escrow.community_vote.clone(),
+            pending_validator_changes: escrow.pending_validator_changes.clone(),
+            validator_history: escrow.validator_history.clone(),
+        })
+    }
+}
+
+fn cw20_coins(balance: &GenericBalance) -> Vec<Cw20Coin> {
+    balance
+        .cw20
+        .iter()
+        .map(|token| Cw20Coin {
+            address: token.address.to_string(),
+            amount: token.amount,
         })
+        .collect()
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: stored.contract,
+        });
+    }
+    // refuse downgrades, and versions we can't make sense of
+    let previous = parse_version(&stored.version);
+    if previous.is_none() || previous > parse_version(CONTRACT_VERSION) {
+        return Err(ContractError::CannotMigrateVersion {
+            previous_version: stored.version,
+        });
+    }
+
+    if previous < Some((0, 2, 0)) {
+        let admin = msg.admin.ok_or(ContractError::MigrationNeedsAdmin {})?;
+        let admin = deps.api.addr_validate(&admin)?;
+        migrate_v0_1(deps.storage)?;
+        let config = build_config(deps.api, admin, InstantiateMsg::default())?;
+        CONFIG.save(deps.storage, &config)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "migrate"),
+        attr("from_version", stored.version),
+        attr("to_version", CONTRACT_VERSION),
+    ]))
+}
+
+/// Rewrites every escrow from the 0.1 layout and builds the secondary indexes
+/// for them, which 0.1 did not have
+fn migrate_v0_1(storage: &mut dyn Storage) -> StdResult<()> {
+    let legacy: Vec<_> = LEGACY_ESCROWS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for (_, escrow) in legacy {
+        let escrow = Escrow::from(escrow);
+        // the stored entry is still in the old layout, so it can't be passed
+        // as old_data for the indexes to clean up. There is nothing to clean up anyway
+        escrows().replace(storage, &escrow.id, Some(&escrow), None)?;
+        // 0.1 refunded everything to the source, so it can claim all of what
+        // is still held. Canceled and completed ones were already paid out
+        if matches!(escrow.status, Status::Opened {} | Status::InProgress {}) {
+            CONTRIBUTIONS.save(storage, (&escrow.id, &escrow.source), &escrow.balance)?;
+        }
+    }
+    Ok(())
+}
+
+/// Splits a "major.minor.patch" version so versions can be compared
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|p| p.parse::<u64>().ok());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => {
+            Some((major, minor, patch))
+        }
+        _ => None,
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::List {} => to_binary(&query_list(deps)?),
-        QueryMsg::ListDetailed {} => to_binary(&query_list_detailed(deps)?),
+        QueryMsg::List {
+            filter,
+            start_after,
+            limit,
+        } => to_binary(&query_list(deps, filter, start_after, limit)?),
+        QueryMsg::ListDetailed {
+            filter,
+            start_after,
+            limit,
+        } => to_binary(&query_list_detailed(deps, filter, start_after, limit)?),
         QueryMsg::Details { id } => to_binary(&query_details(deps, id)?),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::Ballot { id, voter } => to_binary(&query_ballot(deps, id, voter)?),
+        QueryMsg::Contributions {
+            id,
+            start_after,
+            limit,
+        } => to_binary(&query_contributions(deps, id, start_after, limit)?),
     }
 }
 
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let human = |addrs: &[Addr]| addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    Ok(ConfigResponse {
+        admin: config.admin.to_string(),
+        validators: human(&config.validators),
+        proposal_creators: config.proposal_creators.as_deref().map(human),
+        cw20_whitelist: human(&config.cw20_whitelist),
+        min_funding: config.min_funding,
+        max_funding: config.max_funding,
+        exchange: config.exchange.map(|a| a.to_string()),
+        paused: PAUSED.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+fn query_ballot(deps: Deps, id: String, voter: String) -> StdResult<BallotResponse> {
+    let voter = deps.api.addr_validate(&voter)?;
+    let ballot = BALLOTS.may_load(deps.storage, (id.as_str(), &voter))?;
+    Ok(BallotResponse { ballot })
+}
+
+fn query_contributions(
+    deps: Deps,
+    id: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ContributionsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = match start_after {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    let contributions = list_contributions(deps.storage, &id, start_after.as_ref(), limit)?;
+    Ok(ContributionsResponse {
+        contributions: contributions
+            .into_iter()
+            .map(|(contributor, balance)| ContributionResponse {
+                contributor: contributor.to_string(),
+                cw20: cw20_coins(&balance),
+                native: balance.native,
+            })
+            .collect(),
+    })
+}
+
 fn query_details(deps: Deps, id: String) -> StdResult<DetailsResponse> {
-    let escrow = ESCROWS.load(deps.storage, &id)?;
-    Ok(DetailsResponse::from_escrow(&escrow)?)
+    let escrow = escrows().load(deps.storage, &id)?;
+    DetailsResponse::from_escrow(&escrow)
+}
+
+fn query_escrows(
+    deps: Deps,
+    filter: Option<EscrowFilter>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Escrow>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let filter = match filter {
+        Some(filter) => Some(filter.validate(deps.api)?),
+        None => None,
+    };
+    list_escrows(deps.storage, filter.as_ref(), start_after, limit)
 }
 
-fn query_list(deps: Deps) -> StdResult<ListResponse> {
+fn query_list(
+    deps: Deps,
+    filter: Option<EscrowFilter>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListResponse> {
+    let escrows = query_escrows(deps, filter, start_after, limit)?;
     Ok(ListResponse {
-        escrows: all_escrow_ids(deps.storage)?,
+        escrows: escrows.into_iter().map(|e| e.id).collect(),
     })
 }
 
-fn query_list_detailed(deps: Deps) -> StdResult<ListDetailedResponse> {
-    let ids = all_escrow_ids(deps.storage)?;
-    let escrows = all_escrow_details(deps.storage, ids)?;
+fn query_list_detailed(
+    deps: Deps,
+    filter: Option<EscrowFilter>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListDetailedResponse> {
+    let escrows = query_escrows(deps, filter, start_after, limit)?;
     let drs: StdResult<Vec<DetailsResponse>> = escrows
         .iter()
         .map(|e: &Escrow| DetailsResponse::from_escrow(e))
@@ -350,19 +1326,42 @@ fn query_list_detailed(deps: Deps) -> StdResult<ListDetailedResponse> {
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coin, coins, Coin, CosmosMsg, StdError, SubMsg, Uint128};
+    use cosmwasm_std::{coin, coins, StdError, SubMsg, Uint128};
+    use cw0::Expiration;
 
     use crate::msg::ExecuteMsg::TopUp;
 
     use super::*;
     fn mock_topup_cw20_message(id: &String) -> StdResult<ExecuteMsg> {
-        let base = TopUp { id: id.to_string() };
+        let base = TopUp {
+            id: id.to_string(),
+            contributor: None,
+        };
         let top_up = ExecuteMsg::Receive(Cw20ReceiveMsg {
             sender: String::from("random"),
             amount: Uint128::new(7890),
             msg: to_binary(&base).unwrap(),
         });
-        return Ok(top_up);
+        Ok(top_up)
+    }
+
+    fn setup_contract(deps: DepsMut) {
+        let info = mock_info(&String::from("anyone"), &[]);
+        instantiate(deps, mock_env(), info, InstantiateMsg::default()).unwrap();
+    }
+
+    fn accept_proposal(deps: DepsMut, create: &CreateMsg) {
+        let info = mock_info(&create.proposer, &[]);
+        let msg = ExecuteMsg::Accept {
+            id: create.id.clone(),
+        };
+        execute(deps, mock_env(), info, msg).unwrap();
+    }
+
+    fn claim_refund(deps: DepsMut, id: &str, contributor: &str) -> Vec<SubMsg> {
+        let info = mock_info(contributor, &[]);
+        let msg = ExecuteMsg::ClaimRefund { id: id.to_string() };
+        execute(deps, mock_env(), info, msg).unwrap().messages
     }
 
     fn quick_create_msg_cw20() -> (CreateMsg, ExecuteMsg, MessageInfo) {
@@ -371,9 +1370,13 @@ mod tests {
             url: "https://darmstadt.dorium.apeunit.com".to_string(),
             description: String::from("foo to a bar"),
             validators: vec![String::from("validator1"), String::from("validator2")],
+            threshold: 1,
             proposer: String::from("recd"),
-            source: String::from("dorium"),
+            source: Some(String::from("dorium")),
             cw20_whitelist: Some(vec![String::from("other-token")]),
+            milestones: None,
+            expires: None,
+            community_vote: None,
         };
         let receive = Cw20ReceiveMsg {
             sender: String::from("dorium"),
@@ -383,14 +1386,14 @@ mod tests {
         let token_contract = String::from("my-cw20-token");
         let info = mock_info(&token_contract, &[]);
         let msg = ExecuteMsg::Receive(receive.clone());
-        return (create, msg, info);
+        (create, msg, info)
     }
     #[test]
     fn approve_proposal_native_token() {
         let mut deps = mock_dependencies();
 
         // instantiate an empty contract
-        let instantiate_msg = InstantiateMsg {};
+        let instantiate_msg = InstantiateMsg::default();
         let info = mock_info(&String::from("anyone"), &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
         assert_eq!(0, res.messages.len());
@@ -401,9 +1404,13 @@ mod tests {
             url: "https://darmstadt.dorium.apeunit.com".to_string(),
             description: String::from("foo of a bar of a escrow"),
             validators: vec![String::from("validator1"), String::from("validator2")],
+            threshold: 1,
             proposer: String::from("recd"),
-            source: String::from("dorium"),
+            source: Some(String::from("dorium")),
             cw20_whitelist: None,
+            milestones: None,
+            expires: None,
+            community_vote: None,
         };
         let sender = String::from("dorium");
         let balance = coins(100, "tokens");
@@ -422,15 +1429,26 @@ mod tests {
                 url: "https://darmstadt.dorium.apeunit.com".to_string(),
                 description: String::from("foo of a bar of a escrow"),
                 validators: vec![String::from("validator1"), String::from("validator2")],
+                threshold: 1,
+                approvals: vec![],
+                refunds: vec![],
                 proposer: String::from("recd"),
                 source: String::from("dorium"),
                 native_balance: balance.clone(),
                 cw20_balance: vec![],
                 cw20_whitelist: vec![],
                 status: Status::Opened {},
+                milestones: vec![],
+                expires: None,
+                community_vote: None,
+                pending_validator_changes: vec![],
+                validator_history: vec![],
             }
         );
 
+        // the proposer starts working on it
+        accept_proposal(deps.as_mut(), &create);
+
         // approve it
         let id = create.id.clone();
         let info = mock_info(&create.validators[0], &[]);
@@ -451,7 +1469,7 @@ mod tests {
         let mut deps = mock_dependencies();
 
         // instantiate an empty contract
-        let instantiate_msg = InstantiateMsg {};
+        let instantiate_msg = InstantiateMsg::default();
         let info = mock_info(&String::from("anyone"), &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
         assert_eq!(0, res.messages.len());
@@ -462,9 +1480,13 @@ mod tests {
             url: "https://darmstadt.dorium.apeunit.com".to_string(),
             description: String::from("foo to a bar"),
             validators: vec![String::from("validator1"), String::from("validator2")],
+            threshold: 1,
             proposer: String::from("recd"),
-            source: String::from("dorium"),
+            source: Some(String::from("dorium")),
             cw20_whitelist: Some(vec![String::from("other-token")]),
+            milestones: None,
+            expires: None,
+            community_vote: None,
         };
         let receive = Cw20ReceiveMsg {
             sender: String::from("dorium"),
@@ -487,6 +1509,9 @@ mod tests {
                 url: "https://darmstadt.dorium.apeunit.com".to_string(),
                 description: String::from("foo to a bar"),
                 validators: vec![String::from("validator1"), String::from("validator2")],
+                threshold: 1,
+                approvals: vec![],
+                refunds: vec![],
                 proposer: String::from("recd"),
                 source: String::from("dorium"),
                 native_balance: vec![],
@@ -496,9 +1521,17 @@ mod tests {
                 }],
                 cw20_whitelist: vec![String::from("other-token"), String::from("my-cw20-token")],
                 status: Status::Opened {},
+                milestones: vec![],
+                expires: None,
+                community_vote: None,
+                pending_validator_changes: vec![],
+                validator_history: vec![],
             }
         );
 
+        // the proposer starts working on it
+        accept_proposal(deps.as_mut(), &create);
+
         // approve it
         let info = mock_info(&create.validators[0], &[]);
         let res = execute(
@@ -531,7 +1564,7 @@ mod tests {
         let mut deps = mock_dependencies();
 
         // instantiate an empty contract
-        let instantiate_msg = InstantiateMsg {};
+        let instantiate_msg = InstantiateMsg::default();
         let info = mock_info(&String::from("anyone"), &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
         assert_eq!(0, res.messages.len());
@@ -542,9 +1575,13 @@ mod tests {
             url: "https://darmstadt.dorium.apeunit.com".to_string(),
             description: String::from("foo to a bar"),
             validators: vec![String::from("validator1"), String::from("validator2")],
+            threshold: 1,
             proposer: String::from("recd"),
-            source: String::from("dorium"),
+            source: Some(String::from("dorium")),
             cw20_whitelist: Some(vec![String::from("other-token")]),
+            milestones: None,
+            expires: None,
+            community_vote: None,
         };
         let receive = Cw20ReceiveMsg {
             sender: String::from("dorium"),
@@ -562,28 +1599,196 @@ mod tests {
         let id = create.id.clone();
         let info = mock_info(&create.validators[0], &[]);
         let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Refund { id }).unwrap();
-        assert_eq!(1, res.messages.len());
+        assert_eq!(0, res.messages.len());
         assert_eq!(attr("action", "refund"), res.attributes[0]);
 
-        // ensure that the escrow contract told the CW20 contract to burn the tokens
-        let burn_msg = Cw20ExecuteMsg::Burn {
+        // ensure that the escrow contract told the CW20 contract to send the tokens back
+        let transfer_msg = Cw20ExecuteMsg::Transfer {
+            recipient: String::from("dorium"),
             amount: Uint128::new(100),
         };
         assert_eq!(
-            res.messages[0],
-            SubMsg::new(WasmMsg::Execute {
+            claim_refund(deps.as_mut(), &create.id, "dorium"),
+            vec![SubMsg::new(WasmMsg::Execute {
                 contract_addr: token_contract,
-                msg: to_binary(&burn_msg).unwrap(),
+                msg: to_binary(&transfer_msg).unwrap(),
                 funds: vec![],
-            })
+            })]
+        );
+    }
+    #[test]
+    fn refund_goes_to_source() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // Dorium opens one proposal refunding to its treasury, and one refunding to itself
+        let (mut create, _, _) = quick_create_msg_cw20();
+        for (id, source) in &[("treasury", Some("treasury")), ("sender", None)] {
+            create.id = id.to_string();
+            create.source = source.map(String::from);
+            let info = mock_info("dorium", &coins(100, "tokens"));
+            let msg = ExecuteMsg::Create(create.clone());
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        for (id, to) in &[("treasury", "treasury"), ("sender", "dorium")] {
+            let info = mock_info("validator1", &[]);
+            let msg = ExecuteMsg::Refund { id: id.to_string() };
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            assert_eq!(
+                claim_refund(deps.as_mut(), id, to),
+                vec![SubMsg::new(BankMsg::Send {
+                    to_address: to.to_string(),
+                    amount: coins(100, "tokens"),
+                })]
+            );
+            let details = query_details(deps.as_ref(), id.to_string()).unwrap();
+            assert_eq!(details.source, to.to_string());
+        }
+    }
+
+    #[test]
+    fn refund_goes_back_to_contributors() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // half of everything is paid out once the kickoff is approved
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.milestones = Some(vec![MilestoneMsg {
+            title: String::from("kickoff"),
+            amount: MilestoneAmountMsg::Share(Decimal::percent(50)),
+            deadline: None,
+        }]);
+        let info = mock_info("dorium", &coins(100, "tokens"));
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the community chips in with native and cw20 tokens
+        let top_up = ExecuteMsg::TopUp {
+            id: create.id.clone(),
+            contributor: None,
+        };
+        let info = mock_info("alice", &coins(60, "tokens"));
+        execute(deps.as_mut(), mock_env(), info.clone(), top_up.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), info, top_up.clone()).unwrap();
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("bob"),
+            amount: Uint128::new(50),
+            msg: to_binary(&top_up).unwrap(),
+        });
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("other-token", &[]),
+            receive,
+        )
+        .unwrap();
+
+        let msg = QueryMsg::Contributions {
+            id: create.id.clone(),
+            start_after: None,
+            limit: Some(2),
+        };
+        let res: ContributionsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            res.contributions,
+            vec![
+                ContributionResponse {
+                    contributor: String::from("alice"),
+                    native: coins(120, "tokens"),
+                    cw20: vec![],
+                },
+                ContributionResponse {
+                    contributor: String::from("bob"),
+                    native: vec![],
+                    cw20: vec![Cw20Coin {
+                        address: String::from("other-token"),
+                        amount: Uint128::new(50),
+                    }],
+                },
+            ]
+        );
+        let msg = QueryMsg::Contributions {
+            id: create.id.clone(),
+            start_after: Some(String::from("bob")),
+            limit: None,
+        };
+        let res: ContributionsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(1, res.contributions.len());
+        assert_eq!(res.contributions[0].contributor, String::from("dorium"));
+
+        // pay out the kickoff, then give up on the proposal
+        accept_proposal(deps.as_mut(), &create);
+        let info = mock_info(&create.validators[0], &[]);
+        let approve = ExecuteMsg::ApproveMilestone {
+            id: create.id.clone(),
+            milestone: 0,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), approve).unwrap();
+        let claim = ExecuteMsg::ClaimRefund {
+            id: create.id.clone(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            claim.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NotCanceled {});
+        let refund = ExecuteMsg::Refund {
+            id: create.id.clone(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, refund).unwrap();
+        assert!(res.messages.is_empty());
+
+        // everyone gets back half of what they put in, once
+        assert_eq!(
+            claim_refund(deps.as_mut(), &create.id, "alice"),
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: String::from("alice"),
+                amount: coins(60, "tokens"),
+            })]
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), claim).unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+        assert_eq!(
+            claim_refund(deps.as_mut(), &create.id, "bob"),
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: String::from("other-token"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: String::from("bob"),
+                    amount: Uint128::new(25),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+        assert_eq!(
+            claim_refund(deps.as_mut(), &create.id, "dorium"),
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: String::from("dorium"),
+                amount: coins(50, "tokens"),
+            })]
         );
+        let msg = QueryMsg::Contributions {
+            id: create.id.clone(),
+            start_after: None,
+            limit: None,
+        };
+        let res: ContributionsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(res.contributions.is_empty());
     }
+
     #[test]
     fn top_up_mixed_tokens() {
         let mut deps = mock_dependencies();
 
         // instantiate an empty contract
-        let instantiate_msg = InstantiateMsg {};
+        let instantiate_msg = InstantiateMsg::default();
         let info = mock_info(&String::from("anyone"), &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
         assert_eq!(0, res.messages.len());
@@ -597,9 +1802,13 @@ mod tests {
             url: "https://darmstadt.dorium.apeunit.com".to_string(),
             description: String::from("foo to a bar"),
             validators: vec![String::from("validator1"), String::from("validator2")],
+            threshold: 1,
             proposer: String::from("recd"),
-            source: String::from("dorium"),
+            source: Some(String::from("dorium")),
             cw20_whitelist: Some(whitelist),
+            milestones: None,
+            expires: None,
+            community_vote: None,
         };
         let sender = String::from("source");
         let balance = vec![coin(100, "fee"), coin(200, "stake")];
@@ -614,6 +1823,7 @@ mod tests {
         let info = mock_info(&sender, &extra_native);
         let top_up = ExecuteMsg::TopUp {
             id: create.id.clone(),
+            contributor: None,
         };
         let res = execute(deps.as_mut(), mock_env(), info, top_up).unwrap();
         assert_eq!(0, res.messages.len());
@@ -623,6 +1833,7 @@ mod tests {
         let bar_token = String::from("bar_token");
         let base = TopUp {
             id: create.id.clone(),
+            contributor: None,
         };
         let top_up = ExecuteMsg::Receive(Cw20ReceiveMsg {
             sender: String::from("random"),
@@ -639,6 +1850,7 @@ mod tests {
         let baz_token = String::from("baz_token");
         let base = TopUp {
             id: create.id.clone(),
+            contributor: None,
         };
         let top_up = ExecuteMsg::Receive(Cw20ReceiveMsg {
             sender: String::from("random"),
@@ -653,6 +1865,7 @@ mod tests {
         let foo_token = String::from("foo_token");
         let base = TopUp {
             id: create.id.clone(),
+            contributor: None,
         };
         let top_up = ExecuteMsg::Receive(Cw20ReceiveMsg {
             sender: String::from("random"),
@@ -664,6 +1877,9 @@ mod tests {
         assert_eq!(0, res.messages.len());
         assert_eq!(attr("action", "top_up"), res.attributes[0]);
 
+        // the proposer starts working on it
+        accept_proposal(deps.as_mut(), &create);
+
         // approve it
         let id = create.id.clone();
         let info = mock_info(&create.validators[0], &[]);
@@ -713,9 +1929,13 @@ mod tests {
     fn approved_proposal_is_locked() {
         // quickly create a proposal, funding it with cw20 tokens
         let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
         let (create, msg, info) = quick_create_msg_cw20();
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
+        // the proposer starts working on it
+        accept_proposal(deps.as_mut(), &create);
+
         // approve it
         let id = create.id.clone();
         let info = mock_info(&create.validators[0], &[]);
@@ -756,6 +1976,7 @@ mod tests {
     fn rejected_proposal_is_locked() {
         // quickly create a proposal, funding it with cw20 tokens
         let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
         let (create, msg, info) = quick_create_msg_cw20();
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
@@ -794,4 +2015,1257 @@ mod tests {
         .unwrap_err();
         assert!(matches!(err, ContractError::Locked { .. }));
     }
+
+    #[test]
+    fn approve_needs_validator_quorum() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // create an escrow that needs 2 out of 3 validators to agree
+        let create = CreateMsg {
+            id: "foobar".to_string(),
+            url: "https://darmstadt.dorium.apeunit.com".to_string(),
+            description: String::from("foo to a bar"),
+            validators: vec![
+                String::from("validator1"),
+                String::from("validator2"),
+                String::from("validator3"),
+            ],
+            threshold: 2,
+            proposer: String::from("recd"),
+            source: Some(String::from("dorium")),
+            cw20_whitelist: None,
+            milestones: None,
+            expires: None,
+            community_vote: None,
+        };
+        let balance = coins(100, "tokens");
+        let info = mock_info(&String::from("dorium"), &balance);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Create(create.clone()),
+        )
+        .unwrap();
+
+        // the proposer starts working on it
+        accept_proposal(deps.as_mut(), &create);
+
+        // the first vote is only recorded, nothing is paid out
+        let approve = ExecuteMsg::Approve {
+            id: create.id.clone(),
+        };
+        let info = mock_info(&create.validators[0], &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), approve.clone()).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!(attr("votes", "1"), res.attributes[2]);
+        let details = query_details(deps.as_ref(), create.id.clone()).unwrap();
+        assert_eq!(details.status, Status::InProgress {});
+        assert_eq!(details.approvals, vec![String::from("validator1")]);
+
+        // the same validator cannot vote twice, nor for a refund as well
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), approve.clone()).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyVoted {});
+        let refund = ExecuteMsg::Refund {
+            id: create.id.clone(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, refund.clone()).unwrap_err();
+        assert_eq!(err, ContractError::VotedOtherWay {});
+
+        // a validator who voted for a refund can't approve either
+        let info = mock_info(&create.validators[1], &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), refund).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, approve.clone()).unwrap_err();
+        assert_eq!(err, ContractError::VotedOtherWay {});
+
+        // the second validator reaches the threshold and releases the funds
+        let info = mock_info(&create.validators[2], &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, approve).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: create.proposer,
+                amount: balance,
+            })]
+        );
+        let details = query_details(deps.as_ref(), create.id).unwrap();
+        assert_eq!(details.status, Status::Completed {});
+    }
+
+    #[test]
+    fn community_vote_gates_approval() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // SoBz holders have to lock at least 100 in votes, two thirds of them yes
+        let create = CreateMsg {
+            id: "foobar".to_string(),
+            url: "https://darmstadt.dorium.apeunit.com".to_string(),
+            description: String::from("foo to a bar"),
+            validators: vec![String::from("validator1")],
+            threshold: 1,
+            proposer: String::from("recd"),
+            source: Some(String::from("dorium")),
+            cw20_whitelist: None,
+            milestones: None,
+            expires: None,
+            community_vote: Some(CommunityVoteMsg {
+                token: String::from("sobz"),
+                quorum: Uint128::new(100),
+                threshold: Decimal::percent(66),
+            }),
+        };
+        let balance = coins(100, "tokens");
+        let info = mock_info(&String::from("dorium"), &balance);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Create(create.clone()),
+        )
+        .unwrap();
+        accept_proposal(deps.as_mut(), &create);
+
+        let vote = |voter: &str, amount: u128, vote: Vote| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: String::from(voter),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::Vote {
+                    id: create.id.clone(),
+                    vote,
+                })
+                .unwrap(),
+            })
+        };
+        let sobz = mock_info("sobz", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            sobz.clone(),
+            vote("alice", 60, Vote::Yes {}),
+        )
+        .unwrap();
+
+        // votes have to be cast with the right token, and only once
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("other-token", &[]),
+            vote("bob", 40, Vote::Yes {}),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::WrongVoteToken {
+                token: String::from("sobz")
+            }
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            sobz.clone(),
+            vote("alice", 10, Vote::No {}),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::AlreadyVoted {});
+
+        // without the quorum the validators can't approve it yet
+        let approve = ExecuteMsg::Approve {
+            id: create.id.clone(),
+        };
+        let validator = mock_info(&create.validators[0], &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            validator.clone(),
+            approve.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::CommunityVoteNotPassed {});
+
+        // abstaining counts towards the quorum only
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            sobz.clone(),
+            vote("bob", 30, Vote::Abstain {}),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            sobz.clone(),
+            vote("carol", 10, Vote::No {}),
+        )
+        .unwrap();
+        let details = query_details(deps.as_ref(), create.id.clone()).unwrap();
+        let tally = details.community_vote.unwrap().tally;
+        assert_eq!(Uint128::new(100), tally.total());
+
+        // nobody can unlock before the proposal is decided
+        let unlock = ExecuteMsg::Unlock {
+            id: create.id.clone(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            unlock.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::VoteStillOpen {});
+
+        let res = execute(deps.as_mut(), mock_env(), validator, approve).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: create.proposer,
+                amount: balance,
+            })]
+        );
+
+        // and now the voters get their tokens back
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            unlock.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: String::from("sobz"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: String::from("alice"),
+                    amount: Uint128::new(60),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+        let msg = QueryMsg::Ballot {
+            id: create.id.clone(),
+            voter: String::from("alice"),
+        };
+        let res: BallotResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(None, res.ballot);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), unlock).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+    }
+
+    #[test]
+    fn community_vote_gates_milestones() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        let create = CreateMsg {
+            id: "foobar".to_string(),
+            url: "https://darmstadt.dorium.apeunit.com".to_string(),
+            description: String::from("foo to a bar"),
+            validators: vec![String::from("validator1")],
+            threshold: 1,
+            proposer: String::from("recd"),
+            source: Some(String::from("dorium")),
+            cw20_whitelist: None,
+            milestones: Some(vec![MilestoneMsg {
+                title: String::from("kickoff"),
+                amount: MilestoneAmountMsg::Share(Decimal::percent(25)),
+                deadline: None,
+            }]),
+            expires: None,
+            community_vote: Some(CommunityVoteMsg {
+                token: String::from("sobz"),
+                quorum: Uint128::new(100),
+                threshold: Decimal::percent(66),
+            }),
+        };
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Create(create.clone()),
+        )
+        .unwrap();
+        accept_proposal(deps.as_mut(), &create);
+
+        // no milestone is paid out while the vote hasn't passed
+        let approve = ExecuteMsg::ApproveMilestone {
+            id: create.id.clone(),
+            milestone: 0,
+        };
+        let validator = mock_info(&create.validators[0], &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            validator.clone(),
+            approve.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::CommunityVoteNotPassed {});
+
+        let vote = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("alice"),
+            amount: Uint128::new(100),
+            msg: to_binary(&ReceiveMsg::Vote {
+                id: create.id.clone(),
+                vote: Vote::Yes {},
+            })
+            .unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("sobz", &[]), vote).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), validator, approve).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: create.proposer,
+                amount: coins(25, "tokens"),
+            })]
+        );
+    }
+
+    #[test]
+    fn validators_can_be_changed() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // create an escrow that needs 2 out of 3 validators to agree
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.validators = vec![
+            String::from("validator1"),
+            String::from("validator2"),
+            String::from("validator3"),
+        ];
+        create.threshold = 2;
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        accept_proposal(deps.as_mut(), &create);
+        let approve = ExecuteMsg::Approve {
+            id: create.id.clone(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("validator1", &[]),
+            approve,
+        )
+        .unwrap();
+
+        let validator_escrows = |deps: Deps, validator: &str| {
+            let filter = Some(EscrowFilter::Validator(validator.to_string()));
+            query_list(deps, filter, None, None).unwrap().escrows
+        };
+
+        // only the admin and the validators can change validators
+        let add = ExecuteMsg::AddValidator {
+            id: create.id.clone(),
+            validator: String::from("validator4"),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("dorium", &[]),
+            add.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // the admin doesn't need any votes
+        let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), add).unwrap();
+        assert_eq!(attr("added", "validator4"), res.attributes[2]);
+        assert_eq!(
+            validator_escrows(deps.as_ref(), "validator4"),
+            vec![create.id.clone()]
+        );
+
+        // validators need a quorum to remove one of them
+        let remove = ExecuteMsg::RemoveValidator {
+            id: create.id.clone(),
+            validator: String::from("validator1"),
+        };
+        let info = mock_info("validator2", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), remove.clone()).unwrap();
+        assert_eq!(attr("votes", "1"), res.attributes[2]);
+        let err = execute(deps.as_mut(), mock_env(), info, remove.clone()).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyVoted {});
+        let details = query_details(deps.as_ref(), create.id.clone()).unwrap();
+        assert_eq!(1, details.pending_validator_changes.len());
+
+        let info = mock_info("validator3", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, remove).unwrap();
+        assert_eq!(attr("removed", "validator1"), res.attributes[2]);
+        let details = query_details(deps.as_ref(), create.id.clone()).unwrap();
+        assert_eq!(
+            details.validators,
+            vec![
+                String::from("validator2"),
+                String::from("validator3"),
+                String::from("validator4"),
+            ]
+        );
+        // the removed validator's vote is gone too
+        assert!(details.approvals.is_empty());
+        assert!(details.pending_validator_changes.is_empty());
+        assert_eq!(
+            details.validator_history[1],
+            ValidatorChangeRecord {
+                change: ValidatorChange::Remove {
+                    validator: Addr::unchecked("validator1"),
+                },
+                approved_by: vec![Addr::unchecked("validator2"), Addr::unchecked("validator3")],
+                height: mock_env().block.height,
+            }
+        );
+        assert!(validator_escrows(deps.as_ref(), "validator1").is_empty());
+
+        // replacing needs a current validator and a new one
+        let info = mock_info("anyone", &[]);
+        let replace = |old: &str, new: &str| ExecuteMsg::ReplaceValidator {
+            id: create.id.clone(),
+            old: old.to_string(),
+            new: new.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            replace("validator1", "validator5"),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ValidatorNotFound {});
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            replace("validator3", "validator4"),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ValidatorExists {});
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            replace("validator3", "validator5"),
+        )
+        .unwrap();
+        assert!(validator_escrows(deps.as_ref(), "validator3").is_empty());
+        assert_eq!(
+            validator_escrows(deps.as_ref(), "validator5"),
+            vec![create.id.clone()]
+        );
+
+        // the threshold must stay reachable
+        let remove = |validator: &str| ExecuteMsg::RemoveValidator {
+            id: create.id.clone(),
+            validator: validator.to_string(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            remove("validator2"),
+        )
+        .unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, remove("validator4")).unwrap_err();
+        assert_eq!(err, ContractError::BelowThreshold {});
+    }
+
+    #[test]
+    fn create_rejects_unreachable_threshold() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        let (mut create, _, _) = quick_create_msg_cw20();
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+
+        create.threshold = 0;
+        let msg = ExecuteMsg::Create(create.clone());
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidThreshold {});
+
+        create.threshold = 3;
+        let msg = ExecuteMsg::Create(create);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidThreshold {});
+    }
+
+    #[test]
+    fn create_validates_metadata_and_validators() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        let (create, _, _) = quick_create_msg_cw20();
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let mut try_create = |change: &dyn Fn(&mut CreateMsg)| {
+            let mut create = create.clone();
+            change(&mut create);
+            let msg = ExecuteMsg::Create(create);
+            execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err()
+        };
+
+        let err = try_create(&|c| c.id = String::from("ab"));
+        assert_eq!(err, ContractError::InvalidId {});
+        let err = try_create(&|c| c.id = String::from("foo bar"));
+        assert_eq!(err, ContractError::InvalidId {});
+        let err = try_create(&|c| c.url = String::from("ftp://dorium.apeunit.com"));
+        assert_eq!(err, ContractError::InvalidUrl {});
+        let err = try_create(&|c| c.url = format!("https://{}", "a".repeat(MAX_URL_LENGTH)));
+        assert_eq!(
+            err,
+            ContractError::UrlTooLong {
+                max: MAX_URL_LENGTH
+            }
+        );
+        let err = try_create(&|c| c.description = "a".repeat(MAX_DESCRIPTION_LENGTH + 1));
+        assert_eq!(
+            err,
+            ContractError::DescriptionTooLong {
+                max: MAX_DESCRIPTION_LENGTH
+            }
+        );
+        // the config has no default validators to fall back on
+        let err = try_create(&|c| c.validators = vec![]);
+        assert_eq!(err, ContractError::NoValidators {});
+        let err = try_create(&|c| c.validators.push(String::from("validator1")));
+        assert_eq!(
+            err,
+            ContractError::DuplicateValidator {
+                validator: String::from("validator1")
+            }
+        );
+        let err = try_create(&|c| c.proposer = String::from("validator2"));
+        assert_eq!(err, ContractError::ProposerIsValidator {});
+
+        // nor can the proposer be made a validator later on
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let add = ExecuteMsg::AddValidator {
+            id: create.id,
+            validator: create.proposer,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), add).unwrap_err();
+        assert_eq!(err, ContractError::ProposerIsValidator {});
+    }
+
+    #[test]
+    fn milestones_are_paid_out_one_at_a_time() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // a quarter up front, then a fixed amount, and a milestone that's already overdue
+        let create = CreateMsg {
+            id: "foobar".to_string(),
+            url: "https://darmstadt.dorium.apeunit.com".to_string(),
+            description: String::from("foo to a bar"),
+            validators: vec![String::from("validator1"), String::from("validator2")],
+            threshold: 1,
+            proposer: String::from("recd"),
+            source: Some(String::from("dorium")),
+            cw20_whitelist: None,
+            milestones: Some(vec![
+                MilestoneMsg {
+                    title: String::from("kickoff"),
+                    amount: MilestoneAmountMsg::Share(Decimal::percent(25)),
+                    deadline: None,
+                },
+                MilestoneMsg {
+                    title: String::from("prototype"),
+                    amount: MilestoneAmountMsg::Fixed {
+                        native: coins(30, "tokens"),
+                        cw20: vec![],
+                    },
+                    deadline: None,
+                },
+                MilestoneMsg {
+                    title: String::from("too late"),
+                    amount: MilestoneAmountMsg::Share(Decimal::percent(10)),
+                    deadline: Some(Expiration::AtHeight(1)),
+                },
+            ]),
+            expires: None,
+            community_vote: None,
+        };
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the proposer starts working on it
+        accept_proposal(deps.as_mut(), &create);
+
+        // release the first milestone
+        let info = mock_info(&create.validators[0], &[]);
+        let approve = ExecuteMsg::ApproveMilestone {
+            id: create.id.clone(),
+            milestone: 0,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), approve.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: create.proposer.clone(),
+                amount: coins(25, "tokens"),
+            })]
+        );
+
+        // it can't be paid twice
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), approve).unwrap_err();
+        assert_eq!(err, ContractError::MilestoneAlreadyPaid {});
+
+        // release the second milestone
+        let approve = ExecuteMsg::ApproveMilestone {
+            id: create.id.clone(),
+            milestone: 1,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), approve).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: create.proposer.clone(),
+                amount: coins(30, "tokens"),
+            })]
+        );
+
+        // the third one is past its deadline, and there is no fourth one
+        let approve = ExecuteMsg::ApproveMilestone {
+            id: create.id.clone(),
+            milestone: 2,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), approve).unwrap_err();
+        assert_eq!(err, ContractError::MilestoneExpired {});
+        let approve = ExecuteMsg::ApproveMilestone {
+            id: create.id.clone(),
+            milestone: 3,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), approve).unwrap_err();
+        assert_eq!(err, ContractError::MilestoneNotFound {});
+
+        let details = query_details(deps.as_ref(), create.id.clone()).unwrap();
+        assert_eq!(details.native_balance, coins(45, "tokens"));
+        assert_eq!(details.milestones[0].native_paid, coins(25, "tokens"));
+        assert_eq!(details.milestones[1].status, MilestoneStatus::Paid {});
+        assert_eq!(details.milestones[2].status, MilestoneStatus::Pending {});
+
+        // whatever is left can still be refunded to the source
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Refund {
+                id: create.id.clone(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            claim_refund(deps.as_mut(), &create.id, "dorium"),
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: create.source.unwrap(),
+                amount: coins(45, "tokens"),
+            })]
+        );
+    }
+
+    #[test]
+    fn milestone_shares_cannot_exceed_escrow() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.milestones = Some(vec![
+            MilestoneMsg {
+                title: String::from("first half"),
+                amount: MilestoneAmountMsg::Share(Decimal::percent(60)),
+                deadline: None,
+            },
+            MilestoneMsg {
+                title: String::from("second half"),
+                amount: MilestoneAmountMsg::Share(Decimal::percent(50)),
+                deadline: None,
+            },
+        ]);
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let msg = ExecuteMsg::Create(create.clone());
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidMilestones {});
+
+        // fixed amounts have to pay out something
+        for (native, cw20) in [
+            (vec![], vec![]),
+            (coins(0, "tokens"), vec![]),
+            (
+                coins(10, "tokens"),
+                vec![Cw20Coin {
+                    address: String::from("my-cw20-token"),
+                    amount: Uint128::zero(),
+                }],
+            ),
+        ] {
+            create.milestones = Some(vec![MilestoneMsg {
+                title: String::from("prototype"),
+                amount: MilestoneAmountMsg::Fixed { native, cw20 },
+                deadline: None,
+            }]);
+            let msg = ExecuteMsg::Create(create.clone());
+            let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::EmptyMilestone {});
+        }
+    }
+
+    #[test]
+    fn anyone_can_refund_expired_escrow() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // the escrow runs for 10 blocks
+        let mut env = mock_env();
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.expires = Some(Expiration::AtHeight(env.block.height + 10));
+        let balance = coins(100, "tokens");
+        let info = mock_info(&String::from("dorium"), &balance);
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // before it expires, only validators can refund
+        let refund = ExecuteMsg::Refund {
+            id: create.id.clone(),
+        };
+        let info = mock_info(&String::from("random"), &[]);
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), refund.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // once it has expired, it can't be topped up or approved anymore
+        env.block.height += 10;
+        let top_up = ExecuteMsg::TopUp {
+            id: create.id.clone(),
+            contributor: None,
+        };
+        let funder = mock_info(&String::from("dorium"), &coins(5, "tokens"));
+        let err = execute(deps.as_mut(), env.clone(), funder, top_up).unwrap_err();
+        assert_eq!(err, ContractError::Expired {});
+        let approve = ExecuteMsg::Approve {
+            id: create.id.clone(),
+        };
+        let validator = mock_info(&create.validators[0], &[]);
+        let err = execute(deps.as_mut(), env.clone(), validator, approve).unwrap_err();
+        assert_eq!(err, ContractError::Expired {});
+
+        // but anybody can send the funds back to the source
+        execute(deps.as_mut(), env, info, refund).unwrap();
+        assert_eq!(
+            claim_refund(deps.as_mut(), &create.id, "dorium"),
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: create.source.unwrap(),
+                amount: balance,
+            })]
+        );
+    }
+
+    #[test]
+    fn cannot_create_expired_escrow() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.expires = Some(Expiration::AtHeight(1));
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Create(create)).unwrap_err();
+        assert_eq!(err, ContractError::Expired {});
+    }
+
+    #[test]
+    fn proposer_accepts_before_approval() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.threshold = 2;
+        create.milestones = Some(vec![MilestoneMsg {
+            title: String::from("kickoff"),
+            amount: MilestoneAmountMsg::Share(Decimal::percent(50)),
+            deadline: None,
+        }]);
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // work that hasn't been accepted can't be approved
+        let validator = mock_info(&create.validators[0], &[]);
+        let approve = ExecuteMsg::Approve {
+            id: create.id.clone(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), validator.clone(), approve).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidTransition {
+                from: Status::Opened {},
+                to: Status::Completed {},
+            }
+        );
+
+        // only the proposer can accept
+        let accept = ExecuteMsg::Accept {
+            id: create.id.clone(),
+        };
+        let err =
+            execute(deps.as_mut(), mock_env(), validator.clone(), accept.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let proposer = mock_info(&create.proposer, &[]);
+        let res = execute(deps.as_mut(), mock_env(), proposer.clone(), accept.clone()).unwrap();
+        assert_eq!(attr("from_status", "opened"), res.attributes[2]);
+        assert_eq!(attr("to_status", "in_progress"), res.attributes[3]);
+        let err = execute(deps.as_mut(), mock_env(), proposer, accept).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidTransition {
+                from: Status::InProgress {},
+                to: Status::InProgress {},
+            }
+        );
+
+        // a validator can send it back, which drops the approvals so far
+        let approve = ExecuteMsg::Approve {
+            id: create.id.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), validator.clone(), approve).unwrap();
+        let approve_milestone = ExecuteMsg::ApproveMilestone {
+            id: create.id.clone(),
+            milestone: 0,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            validator,
+            approve_milestone.clone(),
+        )
+        .unwrap();
+        let other = mock_info(&create.validators[1], &[]);
+        let reopen = ExecuteMsg::Reopen {
+            id: create.id.clone(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), other.clone(), reopen).unwrap();
+        assert_eq!(attr("to_status", "opened"), res.attributes[3]);
+        let details = query_details(deps.as_ref(), create.id.clone()).unwrap();
+        assert_eq!(details.status, Status::Opened {});
+        assert!(details.approvals.is_empty());
+        assert!(details.milestones[0].approvals.is_empty());
+
+        // so the old milestone vote doesn't count towards the quorum either
+        accept_proposal(deps.as_mut(), &create);
+        let res = execute(deps.as_mut(), mock_env(), other, approve_milestone).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn exchange_tops_up_for_the_funder() {
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            exchange: Some(String::from("exchange")),
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("anyone", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.cw20_whitelist = Some(vec![String::from("sobz")]);
+        let info = mock_info("dorium", &coins(100, "tokens"));
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the exchange mints the SoBz to itself and sends them on for alice,
+        // with the same payload its FundProposal sends
+        let top_up = br#"{"top_up":{"id":"foobar","contributor":"alice"}}"#;
+        let receive = |sender: &str| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: String::from(sender),
+                amount: Uint128::new(30),
+                msg: Binary::from(top_up.to_vec()),
+            })
+        };
+
+        // nobody else can top up in someone else's name
+        let info = mock_info("sobz", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), receive("bob")).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), info, receive("exchange")).unwrap();
+
+        // so once refunded, alice gets the SoBz back, not the exchange
+        let info = mock_info(&create.validators[0], &[]);
+        let refund = ExecuteMsg::Refund {
+            id: create.id.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), info, refund).unwrap();
+        let claim = ExecuteMsg::ClaimRefund {
+            id: create.id.clone(),
+        };
+        let info = mock_info("exchange", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, claim).unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+        assert_eq!(
+            claim_refund(deps.as_mut(), &create.id, "alice"),
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: String::from("sobz"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: String::from("alice"),
+                    amount: Uint128::new(30),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn config_restricts_proposals() {
+        let mut deps = mock_dependencies();
+
+        // only dorium can open proposals, with 10 to 1000 tokens each
+        let instantiate_msg = InstantiateMsg {
+            admin: Some(String::from("admin")),
+            validators: Some(vec![String::from("validator1")]),
+            proposal_creators: Some(vec![String::from("dorium")]),
+            cw20_whitelist: Some(vec![String::from("sobz")]),
+            min_funding: Some(Uint128::new(10)),
+            max_funding: Some(Uint128::new(1000)),
+            exchange: None,
+        };
+        let info = mock_info(&String::from("anyone"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let (mut create, _, _) = quick_create_msg_cw20();
+        create.validators = vec![];
+        create.cw20_whitelist = None;
+        let msg = ExecuteMsg::Create(create.clone());
+
+        let info = mock_info(&String::from("random"), &coins(100, "tokens"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let info = mock_info(&String::from("dorium"), &coins(9, "tokens"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BelowMinFunding {
+                min: Uint128::new(10)
+            }
+        );
+
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the defaults from the config are used
+        let details = query_details(deps.as_ref(), create.id.clone()).unwrap();
+        assert_eq!(details.validators, vec![String::from("validator1")]);
+        assert_eq!(details.cw20_whitelist, vec![String::from("sobz")]);
+
+        // topping up past the maximum fails
+        let top_up = ExecuteMsg::TopUp {
+            id: create.id.clone(),
+            contributor: None,
+        };
+        let info = mock_info(&String::from("random"), &coins(901, "tokens"));
+        let err = execute(deps.as_mut(), mock_env(), info, top_up).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::AboveMaxFunding {
+                max: Uint128::new(1000)
+            }
+        );
+    }
+
+    #[test]
+    fn only_admin_can_update_config() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        let update = ExecuteMsg::UpdateConfig {
+            admin: Some(String::from("dorium")),
+            validators: Some(vec![String::from("validator1")]),
+            proposal_creators: Some(Some(vec![String::from("dorium")])),
+            cw20_whitelist: None,
+            min_funding: None,
+            max_funding: None,
+            exchange: None,
+        };
+        let info = mock_info(&String::from("random"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, update.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let info = mock_info(&String::from("anyone"), &[]);
+        execute(deps.as_mut(), mock_env(), info, update).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(config.admin, String::from("dorium"));
+        assert_eq!(config.proposal_creators, Some(vec![String::from("dorium")]));
+
+        // the minimum can't be above the maximum
+        let update = ExecuteMsg::UpdateConfig {
+            admin: None,
+            validators: None,
+            proposal_creators: None,
+            cw20_whitelist: None,
+            min_funding: Some(Some(Uint128::new(10))),
+            max_funding: Some(Some(Uint128::new(1))),
+            exchange: None,
+        };
+        let info = mock_info(&String::from("dorium"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), update).unwrap_err();
+        assert_eq!(err, ContractError::InvalidFundingLimits {});
+
+        // settings that are left out stay as they were, null unsets them
+        let update = br#"{"update_config":{"max_funding":"500","proposal_creators":null}}"#;
+        let update: ExecuteMsg = from_binary(&Binary::from(update.to_vec())).unwrap();
+        execute(deps.as_mut(), mock_env(), info, update).unwrap();
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.admin, String::from("dorium"));
+        assert_eq!(config.validators, vec![String::from("validator1")]);
+        assert_eq!(config.max_funding, Some(Uint128::new(500)));
+        assert_eq!(config.proposal_creators, None);
+    }
+
+    #[test]
+    fn list_paginated_and_filtered() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        // three proposals, one of which "recd" is already working on
+        let (mut create, _, _) = quick_create_msg_cw20();
+        for (id, proposer) in &[("alpha", "recd"), ("beta", "other"), ("gamma", "recd")] {
+            create.id = id.to_string();
+            create.proposer = proposer.to_string();
+            let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+            let msg = ExecuteMsg::Create(create.clone());
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        create.id = String::from("gamma");
+        accept_proposal(deps.as_mut(), &create);
+
+        let list = |deps: Deps, filter, start_after, limit| {
+            let msg = QueryMsg::List {
+                filter,
+                start_after,
+                limit,
+            };
+            let res: ListResponse = from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.escrows
+        };
+
+        // page through all of them
+        let page = list(deps.as_ref(), None, None, Some(2));
+        assert_eq!(page, vec![String::from("alpha"), String::from("beta")]);
+        let page = list(deps.as_ref(), None, Some(String::from("beta")), Some(2));
+        assert_eq!(page, vec![String::from("gamma")]);
+
+        // or filter them
+        let filter = EscrowFilter::Proposer(String::from("recd"));
+        let page = list(deps.as_ref(), Some(filter.clone()), None, None);
+        assert_eq!(page, vec![String::from("alpha"), String::from("gamma")]);
+        let page = list(
+            deps.as_ref(),
+            Some(filter),
+            Some(String::from("alpha")),
+            None,
+        );
+        assert_eq!(page, vec![String::from("gamma")]);
+        let filter = EscrowFilter::Status(Status::Opened {});
+        let page = list(deps.as_ref(), Some(filter), None, None);
+        assert_eq!(page, vec![String::from("alpha"), String::from("beta")]);
+        let filter = EscrowFilter::Validator(String::from("validator2"));
+        let page = list(deps.as_ref(), Some(filter), None, Some(1));
+        assert_eq!(page, vec![String::from("alpha")]);
+
+        let msg = QueryMsg::ListDetailed {
+            filter: Some(EscrowFilter::Status(Status::InProgress {})),
+            start_after: None,
+            limit: None,
+        };
+        let res: ListDetailedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(1, res.escrows.len());
+        assert_eq!(res.escrows[0].id, String::from("gamma"));
+    }
+
+    #[test]
+    fn paused_contract_only_refunds() {
+        let mut deps = mock_dependencies();
+        setup_contract(deps.as_mut());
+
+        let (create, _, _) = quick_create_msg_cw20();
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let msg = ExecuteMsg::Create(create.clone());
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the admin can pause
+        let info = mock_info(&String::from("random"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Pause {}).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let admin = mock_info(&String::from("anyone"), &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            admin.clone(),
+            ExecuteMsg::Pause {},
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert!(config.paused);
+
+        // no new proposals, top ups or approvals
+        let (mut other, _, _) = quick_create_msg_cw20();
+        other.id = String::from("other");
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Create(other)).unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+        let top_up = ExecuteMsg::TopUp {
+            id: create.id.clone(),
+            contributor: None,
+        };
+        let info = mock_info(&String::from("dorium"), &coins(100, "tokens"));
+        let err = execute(deps.as_mut(), mock_env(), info, top_up).unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+        let top_up = mock_topup_cw20_message(&create.id).unwrap();
+        let info = mock_info(&String::from("my-cw20-token"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, top_up).unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+        let approve = ExecuteMsg::Approve {
+            id: create.id.clone(),
+        };
+        let info = mock_info(&create.validators[0], &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, approve).unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+
+        // but the source can still get its money back
+        let refund = ExecuteMsg::Refund {
+            id: create.id.clone(),
+        };
+        let info = mock_info(&create.validators[0], &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, refund).unwrap();
+        assert_eq!(attr("action", "refund"), res.attributes[0]);
+
+        execute(deps.as_mut(), mock_env(), admin, ExecuteMsg::Unpause {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert!(!config.paused);
+    }
+
+    #[test]
+    fn migrate_escrows_from_v0_1() {
+        let mut deps = mock_dependencies();
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        // an escrow as 0.1 serialized it, under the "escrow" map's length-prefixed namespace
+        deps.storage.set(
+            b"\x00\x06escrowold",
+            br#"{"id":"old","url":"https://darmstadt.dorium.apeunit.com","description":"from before quorums","validators":["validator1"],"proposer":"recd","source":"dorium","balance":{"native":[{"denom":"tokens","amount":"100"}],"cw20":[]},"cw20_whitelist":[],"status":{"Opened":{}}}"#,
+        );
+
+        // 0.1 had no config, so we need to be told who the admin is
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+        assert_eq!(err, ContractError::MigrationNeedsAdmin {});
+        let msg = MigrateMsg {
+            admin: Some(String::from("dorium")),
+        };
+        let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(attr("from_version", "0.1.0"), res.attributes[1]);
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(CONTRACT_VERSION, version.version);
+
+        // rewritten in the current layout, with every field filled in
+        let escrow = escrows().load(&deps.storage, "old").unwrap();
+        assert!(escrow.pending_validator_changes.is_empty());
+        assert!(escrow.validator_history.is_empty());
+        let details = query_details(deps.as_ref(), String::from("old")).unwrap();
+        assert_eq!(1, details.threshold);
+        assert!(details.milestones.is_empty());
+        assert_eq!(coins(100, "tokens"), details.native_balance);
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(String::from("dorium"), config.admin);
+        // 0.1 refunded everything to the source, so that is who can claim it
+        let contributions = list_contributions(&deps.storage, "old", None, 10).unwrap();
+        assert_eq!(
+            vec![(Addr::unchecked("dorium"), escrow.balance.clone())],
+            contributions
+        );
+
+        // the migrated escrow can be found through the new indexes
+        let filter = EscrowFilter::Validator(String::from("validator1"));
+        let list = query_list(deps.as_ref(), Some(filter), None, None).unwrap();
+        assert_eq!(vec![String::from("old")], list.escrows);
+        let filter = EscrowFilter::Status(Status::Opened {});
+        let list = query_list(deps.as_ref(), Some(filter), None, None).unwrap();
+        assert_eq!(vec![String::from("old")], list.escrows);
+
+        // and it works like any other
+        let info = mock_info(&String::from("recd"), &[]);
+        let msg = ExecuteMsg::Accept {
+            id: String::from("old"),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let info = mock_info(&String::from("validator1"), &[]);
+        let msg = ExecuteMsg::Approve {
+            id: String::from("old"),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn migrate_canceled_escrows_from_v0_1() {
+        let mut deps = mock_dependencies();
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        // 0.1 left the balance in place when it refunded a canceled escrow
+        deps.storage.set(
+            b"\x00\x06escrowold",
+            br#"{"id":"old","url":"https://darmstadt.dorium.apeunit.com","description":"from before quorums","validators":["validator1"],"proposer":"recd","source":"dorium","balance":{"native":[{"denom":"tokens","amount":"100"}],"cw20":[]},"cw20_whitelist":[],"status":{"Canceled":{}}}"#,
+        );
+        let msg = MigrateMsg {
+            admin: Some(String::from("dorium")),
+        };
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let details = query_details(deps.as_ref(), String::from("old")).unwrap();
+        assert_eq!(Status::Canceled {}, details.status);
+        assert!(details.native_balance.is_empty());
+        let contributions = list_contributions(&deps.storage, "old", None, 10).unwrap();
+        assert!(contributions.is_empty());
+
+        // so the source can't have it refunded a second time
+        let info = mock_info(&String::from("dorium"), &[]);
+        let msg = ExecuteMsg::ClaimRefund {
+            id: String::from("old"),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+    }
+
+    #[test]
+    fn migrate_refuses_other_contracts_and_downgrades() {
+        let mut deps = mock_dependencies();
+        set_contract_version(deps.as_mut().storage, "crates.io:cw20-escrow", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CannotMigrate {
+                previous_contract: String::from("crates.io:cw20-escrow")
+            }
+        );
+
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CannotMigrateVersion {
+                previous_version: String::from("99.0.0")
+            }
+        );
+
+        // migrating to the same version changes nothing
+        setup_contract(deps.as_mut());
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(String::from("anyone"), config.admin);
+    }
 }
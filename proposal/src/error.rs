@@ -1,6 +1,8 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
+use crate::state::Status;
+
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
     #[error("{0}")]
@@ -21,6 +23,111 @@ pub enum ContractError {
     #[error("Escrow id already in use")]
     AlreadyInUse {},
 
+    #[error("Escrow id must be 3-20 letters, digits, '-' or '_'")]
+    InvalidId {},
+
+    #[error("URL must start with http:// or https://")]
+    InvalidUrl {},
+
+    #[error("URL must be at most {max} bytes")]
+    UrlTooLong { max: usize },
+
+    #[error("Description must be at most {max} bytes")]
+    DescriptionTooLong { max: usize },
+
+    #[error("Escrow needs at least one validator")]
+    NoValidators {},
+
+    #[error("{validator} is listed as a validator more than once")]
+    DuplicateValidator { validator: String },
+
+    #[error("The proposer cannot be a validator of their own escrow")]
+    ProposerIsValidator {},
+
     #[error("Escrow has already been approved/rejected and cannot be changed further")]
     Locked {},
+
+    #[error("Threshold must be between 1 and the number of validators")]
+    InvalidThreshold {},
+
+    #[error("Validator has already voted")]
+    AlreadyVoted {},
+
+    #[error("Validator has already voted the other way")]
+    VotedOtherWay {},
+
+    #[error("Address is already a validator of this escrow")]
+    ValidatorExists {},
+
+    #[error("Address is not a validator of this escrow")]
+    ValidatorNotFound {},
+
+    #[error("Escrow cannot have fewer validators than its threshold")]
+    BelowThreshold {},
+
+    #[error("Community vote threshold must be more than 0 and at most 1")]
+    InvalidCommunityVote {},
+
+    #[error("Escrow has no community vote")]
+    NoCommunityVote {},
+
+    #[error("Community votes must be cast with {token}")]
+    WrongVoteToken { token: String },
+
+    #[error("Community vote has not passed yet")]
+    CommunityVoteNotPassed {},
+
+    #[error("Tokens stay locked until the escrow is completed, canceled or expired")]
+    VoteStillOpen {},
+
+    #[error("Milestone shares must not add up to more than the whole escrow")]
+    InvalidMilestones {},
+
+    #[error("Fixed milestone amounts must have at least one token and no zero amounts")]
+    EmptyMilestone {},
+
+    #[error("Milestone does not exist")]
+    MilestoneNotFound {},
+
+    #[error("Milestone has already been paid")]
+    MilestoneAlreadyPaid {},
+
+    #[error("Milestone deadline has passed")]
+    MilestoneExpired {},
+
+    #[error("Escrow does not hold enough tokens to pay this milestone")]
+    InsufficientFunds {},
+
+    #[error("Proposal cannot go from {from} to {to}")]
+    InvalidTransition { from: Status, to: Status },
+
+    #[error("Escrow has not been refunded")]
+    NotCanceled {},
+
+    #[error("Nothing to claim from this escrow")]
+    NothingToClaim {},
+
+    #[error("Proposal is not in progress")]
+    NotInProgress {},
+
+    #[error("Minimum funding must not be more than the maximum funding")]
+    InvalidFundingLimits {},
+
+    #[error("Proposals must be created with at least {min} of each token")]
+    BelowMinFunding { min: Uint128 },
+
+    #[error("Proposals may not hold more than {max} of any token")]
+    AboveMaxFunding { max: Uint128 },
+
+    #[error("Contract is paused")]
+    Paused {},
+
+    #[error("Cannot migrate from a different contract: {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from version {previous_version}")]
+    CannotMigrateVersion { previous_version: String },
+
+    #[error("An admin must be given when migrating from version 0.1")]
+    MigrationNeedsAdmin {},
 }
@@ -1,66 +1,249 @@
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::state::Status;
-use cosmwasm_std::{Addr, Api, Coin, StdResult};
+use crate::state::{
+    Ballot, CommunityVote, MilestoneAmount, MilestoneStatus, PendingValidatorChange, Status,
+    ValidatorChangeRecord, Vote,
+};
+use cosmwasm_std::{Addr, Api, Coin, Decimal, StdResult, Uint128};
+use cw0::Expiration;
 use cw20::{Cw20Coin, Cw20ReceiveMsg};
-#[derive(Serialize, Deserialize, JsonSchema)]
-pub struct InstantiateMsg {}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct InstantiateMsg {
+    /// the only address that can change the config, defaults to the instantiator
+    pub admin: Option<String>,
+    /// validators assigned to proposals that don't name their own
+    pub validators: Option<Vec<String>>,
+    /// who can open proposals. Anyone can if this is not set
+    pub proposal_creators: Option<Vec<String>>,
+    /// cw20 tokens every proposal accepts, on top of its own whitelist
+    pub cw20_whitelist: Option<Vec<String>>,
+    /// every token sent to create a proposal must be at least this much
+    pub min_funding: Option<Uint128>,
+    /// a proposal may not hold more than this much of any one token
+    pub max_funding: Option<Uint128>,
+    /// the exchange contract, which can top up proposals on behalf of whoever
+    /// paid for the tokens, see ExecuteMsg::TopUp
+    pub exchange: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct MigrateMsg {
+    /// the config admin. Version 0.1 had no config, so this must be given
+    /// when migrating from it, and is ignored otherwise
+    pub admin: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Create(CreateMsg),
-    /// Adds all sent native tokens to the contract
+    /// Adds all sent tokens to the escrow, as a contribution from the sender.
+    /// Only the configured exchange can name another contributor instead
     TopUp {
         id: String,
+        contributor: Option<String>,
+    },
+    /// Accept is called by the proposer to commit to doing the work. This moves
+    /// the proposal from opened to in progress
+    Accept {
+        /// id is a human-readable name for the escrow from create
+        id: String,
+    },
+    /// Reopen lets a validator move an in progress proposal back to opened,
+    /// e.g. when the proposer has stopped working on it. Approval votes cast
+    /// so far are dropped
+    Reopen {
+        /// id is a human-readable name for the escrow from create
+        id: String,
     },
-    /// Approve sends all tokens to the recipient.
-    /// Only the arbiter can do this
+    /// Approve records a validator's vote to send all tokens to the recipient.
+    /// Once `threshold` validators have voted, the tokens are released. Only
+    /// proposals in progress can be approved
     Approve {
         /// id is a human-readable name for the escrow from create
         id: String,
     },
-    /// Refund returns all remaining native tokens to the original sender, or if
-    /// it is a CW20 token, calls burn. The arbiter can do this any time, or
-    /// anyone can do this after a timeout
+    /// Refund records a validator's vote to return all remaining tokens to
+    /// the contributors. Once `threshold` validators have voted, the escrow is
+    /// canceled and each contributor can ClaimRefund. Anyone can do this right
+    /// away once the escrow has expired
     Refund {
         /// id is a human-readable name for the escrow from create
         id: String,
     },
+    /// ClaimRefund pays the sender back their share of a canceled escrow: what
+    /// they put in, scaled down by what milestones had paid out
+    ClaimRefund {
+        /// id is a human-readable name for the escrow from create
+        id: String,
+    },
+    /// ApproveMilestone records a validator's vote to release one milestone's
+    /// funds to the proposer. Once `threshold` validators have voted, the
+    /// milestone is paid out and the rest stays in the escrow. Only proposals
+    /// in progress can have milestones approved
+    ApproveMilestone {
+        /// id is a human-readable name for the escrow from create
+        id: String,
+        /// index of the milestone in the order given at create
+        milestone: u32,
+    },
     /// This accepts a properly-encoded ReceiveMsg from a cw20 contract
     Receive(Cw20ReceiveMsg),
+    /// UpdateConfig changes the config, see InstantiateMsg. Only the admin can
+    /// do this. Settings that are left out stay the same, the optional ones
+    /// are unset by passing null
+    UpdateConfig {
+        admin: Option<String>,
+        validators: Option<Vec<String>>,
+        #[serde(
+            default,
+            deserialize_with = "set_or_unset",
+            skip_serializing_if = "Option::is_none"
+        )]
+        proposal_creators: Option<Option<Vec<String>>>,
+        cw20_whitelist: Option<Vec<String>>,
+        #[serde(
+            default,
+            deserialize_with = "set_or_unset",
+            skip_serializing_if = "Option::is_none"
+        )]
+        min_funding: Option<Option<Uint128>>,
+        #[serde(
+            default,
+            deserialize_with = "set_or_unset",
+            skip_serializing_if = "Option::is_none"
+        )]
+        max_funding: Option<Option<Uint128>>,
+        #[serde(
+            default,
+            deserialize_with = "set_or_unset",
+            skip_serializing_if = "Option::is_none"
+        )]
+        exchange: Option<Option<String>>,
+    },
+    /// Unlock returns the tokens the sender locked in a community vote, once
+    /// the escrow is completed, canceled or expired
+    Unlock {
+        /// id is a human-readable name for the escrow from create
+        id: String,
+    },
+    /// AddValidator gives the escrow another validator. The admin can do this
+    /// right away, otherwise `threshold` validators have to ask for the same
+    /// change. Pending changes are dropped once any change is made
+    AddValidator {
+        /// id is a human-readable name for the escrow from create
+        id: String,
+        validator: String,
+    },
+    /// RemoveValidator takes a validator off the escrow, along with its votes.
+    /// Authorized like AddValidator. The escrow must be left with at least
+    /// `threshold` validators
+    RemoveValidator {
+        /// id is a human-readable name for the escrow from create
+        id: String,
+        validator: String,
+    },
+    /// ReplaceValidator swaps a validator for a new one, dropping the old
+    /// validator's votes. Authorized like AddValidator
+    ReplaceValidator {
+        /// id is a human-readable name for the escrow from create
+        id: String,
+        old: String,
+        new: String,
+    },
+    /// Pause stops everything but refunds and unlocking votes until Unpause
+    /// is called. Only the admin can do this
+    Pause {},
+    Unpause {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ReceiveMsg {
-    Create(CreateMsg),
-    /// Adds all sent native tokens to the contract
+    Create(Box<CreateMsg>),
+    /// Adds all sent tokens to the escrow, as a contribution from the sender.
+    /// Only the configured exchange can name another contributor instead
     TopUp {
         id: String,
+        contributor: Option<String>,
+    },
+    /// Locks the sent tokens as a vote in the escrow's community vote. They
+    /// can be unlocked once the escrow is completed, canceled or expired
+    Vote {
+        id: String,
+        vote: Vote,
     },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct CreateMsg {
     /// id is a human-readable name for the escrow to use later
-    /// 3-20 bytes of utf-8 text
+    /// 3-20 letters, digits, '-' or '_'
     pub id: String,
-    /// URL (there must be a forum post somewhere)
+    /// URL (there must be a forum post somewhere), http(s) and at most
+    /// MAX_URL_LENGTH bytes
     pub url: String,
-    /// more information about this proposal (URL to forum topic?)
+    /// more information about this proposal (URL to forum topic?), at most
+    /// MAX_DESCRIPTION_LENGTH bytes
     pub description: String,
-    /// validators assigned by Dorium can decide to approve or refund the escrow
+    /// validators assigned by Dorium can decide to approve or refund the escrow.
+    /// If empty, the default validators from the config are used. The proposer
+    /// can't be one of them
     pub validators: Vec<String>,
+    /// number of validator votes needed to approve or refund the escrow,
+    /// e.g. 2 out of 3 validators
+    pub threshold: u32,
     /// if approved, funds go to the proposer
     pub proposer: String,
-    /// if refunded, funds go to the source (Dorium)
-    pub source: String,
+    /// if refunded, the funds sent with it go to the source (Dorium). Defaults to whoever
+    /// creates the escrow
+    pub source: Option<String>,
     /// Besides any possible tokens sent with the CreateMsg, this is a list of all cw20 token addresses
     /// that are accepted by the escrow during a top-up. This is required to avoid a DoS attack by topping-up
     /// with an invalid cw20 contract. See https://github.com/CosmWasm/cosmwasm-plus/issues/19
     pub cw20_whitelist: Option<Vec<String>>,
+    /// If set, funds are released to the proposer one milestone at a time
+    pub milestones: Option<Vec<MilestoneMsg>>,
+    /// When set, the escrow can't be funded or approved after this (block
+    /// height or time), and anyone can refund it
+    pub expires: Option<Expiration>,
+    /// When set, holders of the token vote on the proposal as well, and the
+    /// validators can only approve it once that vote has passed
+    pub community_vote: Option<CommunityVoteMsg>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CommunityVoteMsg {
+    /// cw20 token (SoBz) votes are cast with, weighted by the amount sent
+    pub token: String,
+    /// how many tokens have to be locked in votes of any kind for the vote to count
+    pub quorum: Uint128,
+    /// share of the yes and no votes that has to be yes, e.g. "0.5"
+    pub threshold: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MilestoneMsg {
+    /// short description of what has to be delivered
+    pub title: String,
+    /// how much is released to the proposer once the milestone is approved
+    pub amount: MilestoneAmountMsg,
+    /// the milestone can no longer be approved after this
+    pub deadline: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MilestoneAmountMsg {
+    /// a share of everything the escrow has received by the time the milestone
+    /// is approved, e.g. "0.25" for a quarter
+    Share(Decimal),
+    /// fixed amounts of native and cw20 tokens
+    Fixed {
+        native: Vec<Coin>,
+        cw20: Vec<Cw20Coin>,
+    },
 }
 
 impl CreateMsg {
@@ -72,36 +255,131 @@ impl CreateMsg {
     }
 }
 
+/// longest url a proposal can be created with, in bytes
+pub const MAX_URL_LENGTH: usize = 256;
+/// longest description a proposal can be created with, in bytes
+pub const MAX_DESCRIPTION_LENGTH: usize = 1024;
+
+/// Tells a setting given as null, Some(None), apart from one left out, None
+fn set_or_unset<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 pub fn is_valid_name(name: &str) -> bool {
     let bytes = name.as_bytes();
     if bytes.len() < 3 || bytes.len() > 20 {
         return false;
     }
-    true
+    // ids end up in storage keys and links, so keep them plain
+    bytes
+        .iter()
+        .all(|b| b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_')
+}
+
+/// whether the url is an http(s) link to something
+pub fn is_valid_url(url: &str) -> bool {
+    ["https://", "http://"]
+        .iter()
+        .any(|scheme| url.len() > scheme.len() && url.starts_with(scheme))
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    /// Show all open escrows. Return type is ListResponse.
-    List {},
-    /// Show all open escrows and their details. Useful to avoid needing an
-    /// indexing layer for now
-    ListDetailed {},
+    /// Show escrow ids, one page at a time. Return type is ListResponse.
+    List {
+        /// only list escrows matching this
+        filter: Option<EscrowFilter>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Show escrows and their details, one page at a time. Useful to avoid
+    /// needing an indexing layer for now
+    ListDetailed {
+        /// only list escrows matching this
+        filter: Option<EscrowFilter>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
     /// Returns the details of the named escrow, error if not created
     /// Return type: DetailsResponse.
     Details { id: String },
+    /// Returns the contract-wide settings. Return type: ConfigResponse.
+    Config {},
+    /// Returns how a voter voted in an escrow's community vote, if at all.
+    /// Return type: BallotResponse.
+    Ballot { id: String, voter: String },
+    /// Shows what each contributor has put into an escrow, one page at a time.
+    /// Return type: ContributionsResponse.
+    Contributions {
+        id: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigResponse {
+    /// the only address that can change the config
+    pub admin: String,
+    /// validators assigned to proposals that don't name their own
+    pub validators: Vec<String>,
+    /// who can open proposals. Anyone can if this is not set
+    pub proposal_creators: Option<Vec<String>>,
+    /// cw20 tokens every proposal accepts, on top of its own whitelist
+    pub cw20_whitelist: Vec<String>,
+    /// every token sent to create a proposal must be at least this much
+    pub min_funding: Option<Uint128>,
+    /// a proposal may not hold more than this much of any one token
+    pub max_funding: Option<Uint128>,
+    /// the exchange contract, which can top up proposals on behalf of others
+    pub exchange: Option<String>,
+    /// while paused only refunds can be made
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EscrowFilter {
+    /// escrows with this status
+    Status(Status),
+    /// escrows that pay out to this proposer
+    Proposer(String),
+    /// escrows that refund to this source
+    Source(String),
+    /// escrows this validator is assigned to
+    Validator(String),
+}
+
+impl EscrowFilter {
+    /// Validates the address being filtered by, if any
+    pub fn validate(self, api: &dyn Api) -> StdResult<EscrowFilter> {
+        Ok(match self {
+            EscrowFilter::Status(status) => EscrowFilter::Status(status),
+            EscrowFilter::Proposer(addr) => {
+                EscrowFilter::Proposer(api.addr_validate(&addr)?.into())
+            }
+            EscrowFilter::Source(addr) => EscrowFilter::Source(api.addr_validate(&addr)?.into()),
+            EscrowFilter::Validator(addr) => {
+                EscrowFilter::Validator(api.addr_validate(&addr)?.into())
+            }
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct ListResponse {
-    /// list all registered ids
+    /// registered ids on this page
     pub escrows: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct ListDetailedResponse {
-    /// list all registered ids
+    /// registered escrows on this page
     pub escrows: Vec<DetailsResponse>,
 }
 
@@ -116,9 +394,15 @@ pub struct DetailsResponse {
     pub description: String,
     /// validators assigned by Dorium can decide to approve or refund the escrow
     pub validators: Vec<String>,
+    /// number of validator votes needed to approve or refund the escrow
+    pub threshold: u32,
+    /// validators who have voted to approve the escrow
+    pub approvals: Vec<String>,
+    /// validators who have voted to refund the escrow
+    pub refunds: Vec<String>,
     /// if approved, funds go to the proposer
     pub proposer: String,
-    /// if refunded, funds go to the source (Dorium)
+    /// if refunded, what the escrow was created with goes to the source (Dorium)
     pub source: String,
     /// Balance in native tokens
     pub native_balance: Vec<Coin>,
@@ -128,4 +412,54 @@ pub struct DetailsResponse {
     pub cw20_whitelist: Vec<String>,
     /// status of the proposal (enum: opened, in progress, canceled, completed)
     pub status: Status,
+    /// stages in which the funds are released to the proposer
+    pub milestones: Vec<MilestoneResponse>,
+    /// the escrow can't be funded or approved after this, and anyone can refund it
+    pub expires: Option<Expiration>,
+    /// token holders' vote on the proposal, if it has one
+    pub community_vote: Option<CommunityVote>,
+    /// validator changes waiting for more validators to vote for them
+    pub pending_validator_changes: Vec<PendingValidatorChange>,
+    /// validator changes made since the escrow was created, oldest first
+    pub validator_history: Vec<ValidatorChangeRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BallotResponse {
+    /// None if the voter hasn't voted, or has unlocked their tokens
+    pub ballot: Option<Ballot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ContributionsResponse {
+    /// contributions on this page
+    pub contributions: Vec<ContributionResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ContributionResponse {
+    /// who gets these tokens back if the escrow is refunded
+    pub contributor: String,
+    /// native tokens put into the escrow
+    pub native: Vec<Coin>,
+    /// cw20 tokens put into the escrow
+    pub cw20: Vec<Cw20Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MilestoneResponse {
+    /// short description of what has to be delivered
+    pub title: String,
+    /// how much is released to the proposer once the milestone is approved
+    pub amount: MilestoneAmount,
+    /// the milestone can no longer be approved after this
+    pub deadline: Option<Expiration>,
+    /// validators who have voted to approve this milestone
+    pub approvals: Vec<String>,
+    /// native tokens paid out for this milestone so far
+    pub native_paid: Vec<Coin>,
+    /// cw20 tokens paid out for this milestone so far
+    pub cw20_paid: Vec<Cw20Coin>,
+    /// status of the milestone (enum: pending, paid)
+    pub status: MilestoneStatus,
 }
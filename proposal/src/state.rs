@@ -1,8 +1,14 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-use cosmwasm_std::{Addr, Coin, Order, StdError, StdResult, Storage};
-use cw_storage_plus::Map;
+use cosmwasm_std::{
+    Addr, BlockInfo, Coin, Decimal, Empty, Order, StdError, StdResult, Storage, Uint128,
+};
+use cw0::Expiration;
+use cw_storage_plus::{Bound, Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+
+use crate::msg::EscrowFilter;
 
 use cw20::{Balance, Cw20CoinVerified};
 
@@ -45,6 +51,166 @@ impl GenericBalance {
             }
         };
     }
+
+    /// Adds every token in `other` to this balance
+    pub fn add_balance(&mut self, other: &GenericBalance) {
+        if !other.native.is_empty() {
+            self.add_tokens(Balance::from(other.native.clone()));
+        }
+        for token in &other.cw20 {
+            self.add_tokens(Balance::Cw20(token.clone()));
+        }
+    }
+
+    /// Removes every token in `other` from this balance. Fails if this balance
+    /// does not hold enough of any of those tokens
+    pub fn sub_balance(&mut self, other: &GenericBalance) -> StdResult<()> {
+        for token in &other.native {
+            let exist = self
+                .native
+                .iter_mut()
+                .find(|exist| exist.denom == token.denom)
+                .ok_or_else(|| StdError::generic_err(format!("no {} in balance", token.denom)))?;
+            exist.amount = exist.amount.checked_sub(token.amount)?;
+        }
+        for token in &other.cw20 {
+            let exist = self
+                .cw20
+                .iter_mut()
+                .find(|exist| exist.address == token.address)
+                .ok_or_else(|| StdError::generic_err(format!("no {} in balance", token.address)))?;
+            exist.amount = exist.amount.checked_sub(token.amount)?;
+        }
+        // don't keep empty entries around, they would make for invalid transfers
+        self.native.retain(|c| !c.amount.is_zero());
+        self.cw20.retain(|c| !c.amount.is_zero());
+        Ok(())
+    }
+
+    /// Returns `share` of every token in this balance, rounded down. Tokens
+    /// that round down to nothing are left out
+    pub fn share(&self, share: Decimal) -> GenericBalance {
+        GenericBalance {
+            native: self
+                .native
+                .iter()
+                .map(|c| Coin {
+                    denom: c.denom.clone(),
+                    amount: c.amount * share,
+                })
+                .filter(|c| !c.amount.is_zero())
+                .collect(),
+            cw20: self
+                .cw20
+                .iter()
+                .map(|c| Cw20CoinVerified {
+                    address: c.address.clone(),
+                    amount: c.amount * share,
+                })
+                .filter(|c| !c.amount.is_zero())
+                .collect(),
+        }
+    }
+
+    /// Scales every token in this balance by how much of it is `left` out of
+    /// what was `received`, rounded down. Tokens that round down to nothing
+    /// are left out
+    pub fn scaled(&self, left: &GenericBalance, received: &GenericBalance) -> GenericBalance {
+        let native = |balance: &GenericBalance, denom: &str| {
+            let coin = balance.native.iter().find(|c| c.denom == denom);
+            coin.map(|c| c.amount).unwrap_or_default()
+        };
+        let cw20 = |balance: &GenericBalance, address: &Addr| {
+            let coin = balance.cw20.iter().find(|c| &c.address == address);
+            coin.map(|c| c.amount).unwrap_or_default()
+        };
+        GenericBalance {
+            native: self
+                .native
+                .iter()
+                .filter(|c| !native(received, &c.denom).is_zero())
+                .map(|c| Coin {
+                    denom: c.denom.clone(),
+                    amount: c
+                        .amount
+                        .multiply_ratio(native(left, &c.denom), native(received, &c.denom)),
+                })
+                .filter(|c| !c.amount.is_zero())
+                .collect(),
+            cw20: self
+                .cw20
+                .iter()
+                .filter(|c| !cw20(received, &c.address).is_zero())
+                .map(|c| Cw20CoinVerified {
+                    address: c.address.clone(),
+                    amount: c
+                        .amount
+                        .multiply_ratio(cw20(left, &c.address), cw20(received, &c.address)),
+                })
+                .filter(|c| !c.amount.is_zero())
+                .collect(),
+        }
+    }
+
+    /// Caps every token in this balance at the amount of it held by `available`
+    pub fn capped_at(&self, available: &GenericBalance) -> GenericBalance {
+        GenericBalance {
+            native: self
+                .native
+                .iter()
+                .filter_map(|c| {
+                    let exist = available.native.iter().find(|a| a.denom == c.denom)?;
+                    Some(Coin {
+                        denom: c.denom.clone(),
+                        amount: c.amount.min(exist.amount),
+                    })
+                })
+                .collect(),
+            cw20: self
+                .cw20
+                .iter()
+                .filter_map(|c| {
+                    let exist = available.cw20.iter().find(|a| a.address == c.address)?;
+                    Some(Cw20CoinVerified {
+                        address: c.address.clone(),
+                        amount: c.amount.min(exist.amount),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MilestoneAmount {
+    /// a share of everything the escrow has received by the time the milestone
+    /// is approved, e.g. "0.25" for a quarter
+    Share(Decimal),
+    /// fixed amounts of native and cw20 tokens
+    Fixed(GenericBalance),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum MilestoneStatus {
+    Pending {},
+    Paid {},
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Milestone {
+    /// short description of what has to be delivered
+    pub title: String,
+    /// how much is released to the proposer once the milestone is approved
+    pub amount: MilestoneAmount,
+    /// the milestone can no longer be approved after this
+    pub deadline: Option<Expiration>,
+    /// validators who have voted to approve this milestone
+    pub approvals: Vec<Addr>,
+    /// what has been released to the proposer for this milestone
+    pub paid: GenericBalance,
+    /// status of the milestone (enum: pending, paid)
+    pub status: MilestoneStatus,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -54,6 +220,33 @@ pub enum Status {
     Canceled {},
     Completed {},
 }
+
+impl Status {
+    /// The proposal lifecycle: a proposal is opened, the proposer accepts it
+    /// and works on it (validators may send it back to opened), and it is
+    /// completed once approved. Until then it can be canceled at any time.
+    pub fn can_transition_to(&self, to: &Status) -> bool {
+        matches!(
+            (self, to),
+            (Status::Opened {}, Status::InProgress {})
+                | (Status::InProgress {}, Status::Opened {})
+                | (Status::InProgress {}, Status::Completed {})
+                | (Status::Opened {}, Status::Canceled {})
+                | (Status::InProgress {}, Status::Canceled {})
+        )
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Opened {} => write!(f, "opened"),
+            Status::InProgress {} => write!(f, "in_progress"),
+            Status::Canceled {} => write!(f, "canceled"),
+            Status::Completed {} => write!(f, "completed"),
+        }
+    }
+}
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct Escrow {
     /// id is a human-readable name. It is the key in the mapping to store the
@@ -65,16 +258,35 @@ pub struct Escrow {
     pub description: String,
     /// validators assigned by Dorium can decide to approve or refund the escrow
     pub validators: Vec<Addr>,
+    /// number of validator votes needed before the escrow is approved or refunded
+    pub threshold: u32,
+    /// validators who have voted to approve the escrow
+    pub approvals: Vec<Addr>,
+    /// validators who have voted to refund the escrow
+    pub refunds: Vec<Addr>,
     /// if approved, funds go to the proposer
     pub proposer: Addr,
-    /// if refunded, funds go to the source (Dorium)
+    /// if refunded, what the escrow was created with goes to the source (Dorium)
     pub source: Addr,
-    /// Balance in Native and Cw20 tokens
+    /// Balance in Native and Cw20 tokens. Once canceled, it is what was left
+    /// to refund and no longer changes, see ExecuteMsg::ClaimRefund
     pub balance: GenericBalance,
     /// All possible contracts that we accept tokens from
     pub cw20_whitelist: Vec<Addr>,
     /// status of the proposal (enum: opened, in progress, canceled, completed)
     pub status: Status,
+    /// stages in which the funds are released to the proposer
+    pub milestones: Vec<Milestone>,
+    /// when set, the escrow can't be funded or approved after this, and
+    /// anyone can refund it
+    pub expires: Option<Expiration>,
+    /// when set, token holders vote on the proposal too, and it can only be
+    /// approved once that vote has passed
+    pub community_vote: Option<CommunityVote>,
+    /// validator changes some validators have asked for, but not enough yet
+    pub pending_validator_changes: Vec<PendingValidatorChange>,
+    /// validator changes made since the escrow was created, oldest first
+    pub validator_history: Vec<ValidatorChangeRecord>,
 }
 
 impl Escrow {
@@ -86,33 +298,366 @@ impl Escrow {
         self.validators.iter().map(|a| a.to_string()).collect()
     }
 
+    /// everything that has been paid out for milestones so far
+    pub fn milestones_paid(&self) -> GenericBalance {
+        let mut paid = GenericBalance::default();
+        for milestone in &self.milestones {
+            paid.add_balance(&milestone.paid);
+        }
+        paid
+    }
+
+    /// whether the escrow has run out of time, see `expires`
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        match &self.expires {
+            Some(expires) => expires.is_expired(block),
+            None => false,
+        }
+    }
+
     /// whether this proposal can be changed any further (once approved/rejected, it will be locked)
     pub fn locked(&self) -> bool {
-        match self.status {
-            Status::Canceled {} => true,
-            Status::Completed {} => true,
-            _ => false,
+        matches!(self.status, Status::Canceled {} | Status::Completed {})
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidatorChange {
+    Add { validator: Addr },
+    Remove { validator: Addr },
+    Replace { old: Addr, new: Addr },
+}
+
+impl ValidatorChange {
+    /// the validator that gets a seat, if any
+    pub fn added(&self) -> Option<&Addr> {
+        match self {
+            ValidatorChange::Add { validator } => Some(validator),
+            ValidatorChange::Remove { .. } => None,
+            ValidatorChange::Replace { new, .. } => Some(new),
+        }
+    }
+
+    /// the validator that loses its seat, if any
+    pub fn removed(&self) -> Option<&Addr> {
+        match self {
+            ValidatorChange::Add { .. } => None,
+            ValidatorChange::Remove { validator } => Some(validator),
+            ValidatorChange::Replace { old, .. } => Some(old),
         }
     }
 }
 
-pub const ESCROWS: Map<&str, Escrow> = Map::new("escrow");
+/// PendingValidatorChange is a validator change that is waiting for
+/// `threshold` validators to vote for it
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingValidatorChange {
+    pub change: ValidatorChange,
+    /// validators who have voted for this change
+    pub approvals: Vec<Addr>,
+}
+
+/// ValidatorChangeRecord is a validator change that has been made
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ValidatorChangeRecord {
+    pub change: ValidatorChange,
+    /// the admin, or the validators who voted for the change
+    pub approved_by: Vec<Addr>,
+    /// block height the change was made at
+    pub height: u64,
+}
+
+/// CommunityVote lets holders of a cw20 token (SoBz) vote on a proposal. Votes
+/// are weighted by the tokens locked with them, which are returned once the
+/// escrow is completed, canceled or expired
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CommunityVote {
+    /// the token votes are cast with
+    pub token: Addr,
+    /// how many tokens have to be locked in votes of any kind for the vote to count
+    pub quorum: Uint128,
+    /// share of the yes and no votes that has to be yes, e.g. "0.5"
+    pub threshold: Decimal,
+    /// tokens locked for each option so far
+    pub tally: Tally,
+}
+
+impl CommunityVote {
+    /// whether the quorum has been reached and enough of the votes are yes
+    pub fn passed(&self) -> bool {
+        let tally = &self.tally;
+        tally.total() >= self.quorum
+            && !tally.yes.is_zero()
+            && tally.yes >= (tally.yes + tally.no) * self.threshold
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct Tally {
+    pub yes: Uint128,
+    pub no: Uint128,
+    pub abstain: Uint128,
+}
+
+impl Tally {
+    pub fn add(&mut self, vote: &Vote, weight: Uint128) {
+        match vote {
+            Vote::Yes {} => self.yes += weight,
+            Vote::No {} => self.no += weight,
+            Vote::Abstain {} => self.abstain += weight,
+        }
+    }
+
+    pub fn total(&self) -> Uint128 {
+        self.yes + self.no + self.abstain
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Vote {
+    Yes {},
+    No {},
+    /// counts towards the quorum, but not for or against
+    Abstain {},
+}
+
+/// Ballot is one voter's vote in a community vote, and the tokens locked with it
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Ballot {
+    pub vote: Vote,
+    pub weight: Uint128,
+}
+
+/// ballots cast in community votes, by escrow id and voter
+pub const BALLOTS: Map<(&str, &Addr), Ballot> = Map::new("ballots");
 
-/// This returns the list of ids for all registered escrows
-pub fn all_escrow_ids(storage: &dyn Storage) -> StdResult<Vec<String>> {
-    ESCROWS
-        .keys(storage, None, None, Order::Ascending)
-        .map(|k| String::from_utf8(k).map_err(|_| StdError::invalid_utf8("parsing escrow key")))
+/// what each contributor has put into an escrow, by escrow id and contributor.
+/// Funds an escrow is created with count for its source. Removed once the
+/// contributor has claimed their refund
+pub const CONTRIBUTIONS: Map<(&str, &Addr), GenericBalance> = Map::new("contributions");
+
+/// Returns up to `limit` contributions to the escrow, from contributors after
+/// `start_after` in ascending order
+pub fn list_contributions(
+    storage: &dyn Storage,
+    id: &str,
+    start_after: Option<&Addr>,
+    limit: usize,
+) -> StdResult<Vec<(Addr, GenericBalance)>> {
+    let start = start_after.map(|a| Bound::exclusive(a.as_str()));
+    CONTRIBUTIONS
+        .prefix(id)
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (contributor, balance) = item?;
+            let contributor = String::from_utf8(contributor)
+                .map_err(|_| StdError::invalid_utf8("parsing contributor key"))?;
+            Ok((Addr::unchecked(contributor), balance))
+        })
         .collect()
 }
 
-/// This returns all registered escrows
-pub fn all_escrow_details(storage: &dyn Storage, keys: Vec<String>) -> StdResult<Vec<Escrow>> {
-    let mut ans: Vec<Escrow> = Vec::new();
-    for key in keys {
-        ans.push(ESCROWS.load(storage, &key)?);
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Config {
+    /// the only address that can change this config
+    pub admin: Addr,
+    /// validators assigned to proposals that don't name their own
+    pub validators: Vec<Addr>,
+    /// who can open proposals. Anyone can if this is not set
+    pub proposal_creators: Option<Vec<Addr>>,
+    /// cw20 tokens every proposal accepts, on top of its own whitelist
+    pub cw20_whitelist: Vec<Addr>,
+    /// every token sent to create a proposal must be at least this much
+    pub min_funding: Option<Uint128>,
+    /// a proposal may not hold more than this much of any one token
+    pub max_funding: Option<Uint128>,
+    /// the exchange contract, which can top up proposals on behalf of others
+    pub exchange: Option<Addr>,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+/// while paused only refunds can be made, see ExecuteMsg::Pause
+pub const PAUSED: Item<bool> = Item::new("paused");
+
+pub struct EscrowIndexes<'a> {
+    pub status: MultiIndex<'a, (Vec<u8>, Vec<u8>), Escrow>,
+    pub proposer: MultiIndex<'a, (Vec<u8>, Vec<u8>), Escrow>,
+    pub source: MultiIndex<'a, (Vec<u8>, Vec<u8>), Escrow>,
+    pub validator: ValidatorIndex<'a>,
+}
+
+impl<'a> IndexList<Escrow> for EscrowIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Escrow>> + '_> {
+        let v: Vec<&dyn Index<Escrow>> =
+            vec![&self.status, &self.proposer, &self.source, &self.validator];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Escrows are stored by id, with secondary indexes to look them up by status,
+/// proposer, source and validator
+pub fn escrows<'a>() -> IndexedMap<'a, &'a str, Escrow, EscrowIndexes<'a>> {
+    let indexes = EscrowIndexes {
+        status: MultiIndex::new(
+            |e, k| (e.status.to_string().into_bytes(), k),
+            "escrow",
+            "escrow__status",
+        ),
+        proposer: MultiIndex::new(
+            |e, k| (e.proposer.as_bytes().to_vec(), k),
+            "escrow",
+            "escrow__proposer",
+        ),
+        source: MultiIndex::new(
+            |e, k| (e.source.as_bytes().to_vec(), k),
+            "escrow",
+            "escrow__source",
+        ),
+        validator: ValidatorIndex::new("escrow__validator"),
+    };
+    IndexedMap::new("escrow", indexes)
+}
+
+/// Indexes escrows by each of their validators. A MultiIndex can only file an
+/// escrow under one key, but an escrow has several validators.
+pub struct ValidatorIndex<'a> {
+    idx_map: Map<'a, (Vec<u8>, Vec<u8>), Empty>,
+}
+
+impl<'a> ValidatorIndex<'a> {
+    pub const fn new(idx_namespace: &'a str) -> Self {
+        ValidatorIndex {
+            idx_map: Map::new(idx_namespace),
+        }
+    }
+
+    /// Returns up to `limit` ids of escrows the validator is assigned to, in ascending order
+    pub fn escrow_ids(
+        &self,
+        storage: &dyn Storage,
+        validator: &Addr,
+        start: Option<Bound>,
+        limit: usize,
+    ) -> StdResult<Vec<String>> {
+        self.idx_map
+            .prefix(validator.as_bytes().to_vec())
+            .keys(storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|k| String::from_utf8(k).map_err(|_| StdError::invalid_utf8("parsing escrow key")))
+            .collect()
+    }
+}
+
+impl<'a> Index<Escrow> for ValidatorIndex<'a> {
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &Escrow) -> StdResult<()> {
+        for validator in &data.validators {
+            let key = (validator.as_bytes().to_vec(), pk.to_vec());
+            self.idx_map.save(store, key, &Empty {})?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &Escrow) -> StdResult<()> {
+        for validator in &old_data.validators {
+            let key = (validator.as_bytes().to_vec(), pk.to_vec());
+            self.idx_map.remove(store, key);
+        }
+        Ok(())
     }
-    return Ok(ans);
+}
+
+/// Escrow as stored by version 0.1 of this contract, before quorums, milestones
+/// and expiration were added. Only read when migrating
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LegacyEscrow {
+    pub id: String,
+    pub url: String,
+    pub description: String,
+    pub validators: Vec<Addr>,
+    pub proposer: Addr,
+    pub source: Addr,
+    pub balance: GenericBalance,
+    pub cw20_whitelist: Vec<Addr>,
+    pub status: Status,
+}
+
+impl From<LegacyEscrow> for Escrow {
+    fn from(escrow: LegacyEscrow) -> Escrow {
+        // 0.1 paid everything out when an escrow was canceled or completed,
+        // whatever balance it left behind has already been sent
+        let balance = match escrow.status {
+            Status::Opened {} | Status::InProgress {} => escrow.balance,
+            Status::Canceled {} | Status::Completed {} => GenericBalance::default(),
+        };
+        Escrow {
+            id: escrow.id,
+            url: escrow.url,
+            description: escrow.description,
+            validators: escrow.validators,
+            // any single validator could decide in 0.1
+            threshold: 1,
+            approvals: vec![],
+            refunds: vec![],
+            proposer: escrow.proposer,
+            source: escrow.source,
+            balance,
+            cw20_whitelist: escrow.cw20_whitelist,
+            status: escrow.status,
+            milestones: vec![],
+            expires: None,
+            community_vote: None,
+            pending_validator_changes: vec![],
+            validator_history: vec![],
+        }
+    }
+}
+
+/// the same entries as escrows(), in the 0.1 layout
+pub const LEGACY_ESCROWS: Map<&str, LegacyEscrow> = Map::new("escrow");
+
+/// This returns up to `limit` escrows with an id after `start_after`, in
+/// ascending order. If a filter is given, only matching escrows are returned
+pub fn list_escrows(
+    storage: &dyn Storage,
+    filter: Option<&EscrowFilter>,
+    start_after: Option<String>,
+    limit: usize,
+) -> StdResult<Vec<Escrow>> {
+    let start = start_after.map(Bound::exclusive);
+    let escrows = escrows();
+    let range = match filter {
+        None => escrows.range(storage, start, None, Order::Ascending),
+        Some(EscrowFilter::Status(status)) => escrows
+            .idx
+            .status
+            .prefix(status.to_string().into_bytes())
+            .range(storage, start, None, Order::Ascending),
+        Some(EscrowFilter::Proposer(proposer)) => escrows
+            .idx
+            .proposer
+            .prefix(proposer.as_bytes().to_vec())
+            .range(storage, start, None, Order::Ascending),
+        Some(EscrowFilter::Source(source)) => escrows
+            .idx
+            .source
+            .prefix(source.as_bytes().to_vec())
+            .range(storage, start, None, Order::Ascending),
+        Some(EscrowFilter::Validator(validator)) => {
+            let validator = Addr::unchecked(validator);
+            let ids = escrows
+                .idx
+                .validator
+                .escrow_ids(storage, &validator, start, limit)?;
+            return ids.iter().map(|id| escrows.load(storage, id)).collect();
+        }
+    };
+    range
+        .take(limit)
+        .map(|item| item.map(|(_, escrow)| escrow))
+        .collect()
 }
 
 #[cfg(test)]
@@ -123,10 +668,10 @@ mod tests {
     use cosmwasm_std::{coin, Uint128};
 
     #[test]
-    fn no_escrow_ids() {
+    fn no_escrows() {
         let storage = MockStorage::new();
-        let ids = all_escrow_ids(&storage).unwrap();
-        assert_eq!(0, ids.len());
+        let escrows = list_escrows(&storage, None, None, 10).unwrap();
+        assert_eq!(0, escrows.len());
     }
 
     fn dummy_escrow() -> Escrow {
@@ -135,11 +680,19 @@ mod tests {
             url: "https://darmstadt.dorium.apeunit.com".to_string(),
             description: "more information about the test here".to_string(),
             validators: vec![Addr::unchecked("arb")],
+            threshold: 1,
+            approvals: vec![],
+            refunds: vec![],
             proposer: Addr::unchecked("proposer"),
             source: Addr::unchecked("source"),
             balance: Default::default(),
             cw20_whitelist: vec![Addr::unchecked("Cw20 Value Token")],
             status: Status::Opened {},
+            milestones: vec![],
+            expires: None,
+            community_vote: None,
+            pending_validator_changes: vec![],
+            validator_history: vec![],
         }
     }
 
@@ -187,21 +740,119 @@ mod tests {
     }
 
     #[test]
-    fn all_escrow_ids_in_order() {
-        let mut storage = MockStorage::new();
-        ESCROWS
-            .save(&mut storage, &"lazy", &dummy_escrow())
-            .unwrap();
-        ESCROWS
-            .save(&mut storage, &"assign", &dummy_escrow())
+    fn status_transitions() {
+        let opened = Status::Opened {};
+        let in_progress = Status::InProgress {};
+        assert!(opened.can_transition_to(&in_progress));
+        assert!(in_progress.can_transition_to(&opened));
+        assert!(in_progress.can_transition_to(&Status::Completed {}));
+        assert!(opened.can_transition_to(&Status::Canceled {}));
+
+        // work has to be accepted before it can be completed
+        assert!(!opened.can_transition_to(&Status::Completed {}));
+        // and once it's over, it's over
+        assert!(!Status::Completed {}.can_transition_to(&in_progress));
+        assert!(!Status::Canceled {}.can_transition_to(&opened));
+        assert_eq!("in_progress", in_progress.to_string());
+    }
+
+    #[test]
+    fn sub_balance_and_share() {
+        let foo_token = Addr::unchecked("foo_token");
+        let mut tokens = GenericBalance {
+            native: vec![coin(100, "atom"), coin(3, "eth")],
+            cw20: vec![Cw20CoinVerified {
+                address: foo_token.clone(),
+                amount: Uint128::new(1000),
+            }],
+        };
+
+        // a quarter of everything, eth rounds down to nothing
+        let quarter = tokens.share(Decimal::percent(25));
+        assert_eq!(quarter.native, vec![coin(25, "atom")]);
+        assert_eq!(quarter.cw20[0].amount, Uint128::new(250));
+
+        tokens.sub_balance(&quarter).unwrap();
+        assert_eq!(tokens.native, vec![coin(75, "atom"), coin(3, "eth")]);
+        assert_eq!(tokens.cw20[0].amount, Uint128::new(750));
+
+        // empty entries are removed
+        tokens
+            .sub_balance(&GenericBalance {
+                native: vec![coin(3, "eth")],
+                cw20: vec![],
+            })
             .unwrap();
-        ESCROWS.save(&mut storage, &"zen", &dummy_escrow()).unwrap();
+        assert_eq!(tokens.native, vec![coin(75, "atom")]);
 
-        let ids = all_escrow_ids(&storage).unwrap();
-        assert_eq!(3, ids.len());
+        // can't take out more than there is
+        let too_much = GenericBalance {
+            native: vec![coin(76, "atom")],
+            cw20: vec![],
+        };
+        tokens.clone().sub_balance(&too_much).unwrap_err();
+        assert_eq!(too_much.capped_at(&tokens).native, vec![coin(75, "atom")]);
+    }
+
+    #[test]
+    fn list_escrows_in_order() {
+        let mut storage = MockStorage::new();
+        for id in &["lazy", "assign", "zen"] {
+            let mut escrow = dummy_escrow();
+            escrow.id = id.to_string();
+            escrows().save(&mut storage, id, &escrow).unwrap();
+        }
+
+        let ids = |escrows: Vec<Escrow>| escrows.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        let all = list_escrows(&storage, None, None, 10).unwrap();
         assert_eq!(
             vec!["assign".to_string(), "lazy".to_string(), "zen".to_string()],
-            ids
-        )
+            ids(all)
+        );
+
+        // page through them
+        let page = list_escrows(&storage, None, Some("assign".to_string()), 1).unwrap();
+        assert_eq!(vec!["lazy".to_string()], ids(page));
+    }
+
+    #[test]
+    fn list_escrows_by_index() {
+        let mut storage = MockStorage::new();
+        let mut first = dummy_escrow();
+        first.id = "first".to_string();
+        first.validators = vec![Addr::unchecked("arb"), Addr::unchecked("other")];
+        escrows().save(&mut storage, "first", &first).unwrap();
+        let mut second = dummy_escrow();
+        second.id = "second".to_string();
+        second.proposer = Addr::unchecked("someone else");
+        second.status = Status::InProgress {};
+        escrows().save(&mut storage, "second", &second).unwrap();
+
+        let filter = EscrowFilter::Status(Status::InProgress {});
+        let found = list_escrows(&storage, Some(&filter), None, 10).unwrap();
+        assert_eq!(vec![second.clone()], found);
+
+        let filter = EscrowFilter::Proposer("proposer".to_string());
+        let found = list_escrows(&storage, Some(&filter), None, 10).unwrap();
+        assert_eq!(vec![first.clone()], found);
+
+        let filter = EscrowFilter::Source("source".to_string());
+        let found = list_escrows(&storage, Some(&filter), None, 10).unwrap();
+        assert_eq!(vec![first.clone(), second.clone()], found);
+
+        let filter = EscrowFilter::Validator("other".to_string());
+        let found = list_escrows(&storage, Some(&filter), None, 10).unwrap();
+        assert_eq!(vec![first.clone()], found);
+
+        // the indexes follow changes to the escrow
+        first.status = Status::InProgress {};
+        first.validators = vec![Addr::unchecked("arb")];
+        escrows().save(&mut storage, "first", &first).unwrap();
+        let filter = EscrowFilter::Status(Status::InProgress {});
+        let found = list_escrows(&storage, Some(&filter), None, 10).unwrap();
+        assert_eq!(vec![first, second], found);
+        let filter = EscrowFilter::Validator("other".to_string());
+        let found = list_escrows(&storage, Some(&filter), None, 10).unwrap();
+        assert!(found.is_empty());
     }
 }